@@ -1,3 +1,4 @@
+use crate::mat4::*;
 use crate::ray::*;
 use crate::vec3::*;
 use std::mem;
@@ -14,7 +15,7 @@ impl AABB {
         Self { min, max }
     }
 
-    pub fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> bool {
+    pub fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
         for a in 0..3 {
             let invd = 1.0 / r.direction[a];
 
@@ -25,8 +26,8 @@ impl AABB {
                 mem::swap(&mut t0, &mut t1);
             }
 
-            let t_min_p = f32::max(t0, t_min);
-            let t_max_p = f32::min(t1, t_max);
+            let t_min_p = Float::max(t0, t_min);
+            let t_max_p = Float::min(t1, t_max);
 
             if t_max_p <= t_min_p {
                 return false;
@@ -36,24 +37,266 @@ impl AABB {
         true
     }
 
+    // The same test as hit(), with 1 / r.direction already worked out.
+    #[inline]
+    pub fn hit_with_inverse(&self, r: &Ray, inv_dir: &Vec3, t_min: Float, t_max: Float) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+
+        for a in 0..3 {
+            let mut t0 = (self.min[a] - r.origin[a]) * inv_dir[a];
+            let mut t1 = (self.max[a] - r.origin[a]) * inv_dir[a];
+
+            if inv_dir[a] < 0.0 {
+                mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // The part of [t_min, t_max] where the ray is inside the box.
+    pub fn clip(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Float)> {
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+
+        for a in 0..3 {
+            let invd = 1.0 / r.direction[a];
+            let mut near = (self.min[a] - r.origin[a]) * invd;
+            let mut far = (self.max[a] - r.origin[a]) * invd;
+
+            if invd < 0.0 {
+                mem::swap(&mut near, &mut far);
+            }
+
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+
+            if t1 <= t0 {
+                return None;
+            }
+        }
+
+        Some((t0, t1))
+    }
+
+    pub fn surface_area(&self) -> Float {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
     pub fn surrounding_box(box0: &AABB, box1: &AABB) -> Self {
         let small = point3(
-            f32::min(box0.min.x, box1.min.x),
-            f32::min(box0.min.y, box1.min.y),
-            f32::min(box0.min.z, box1.min.z),
+            Float::min(box0.min.x, box1.min.x),
+            Float::min(box0.min.y, box1.min.y),
+            Float::min(box0.min.z, box1.min.z),
         );
 
         let big = point3(
-            f32::max(box0.max.x, box1.max.x),
-            f32::max(box0.max.y, box1.max.y),
-            f32::max(box0.max.z, box1.max.z),
+            Float::max(box0.max.x, box1.max.x),
+            Float::max(box0.max.y, box1.max.y),
+            Float::max(box0.max.z, box1.max.z),
         );
 
         aabb(small, big)
     }
+
+    // The box surrounding all eight corners of this box under `m`.
+    pub fn transformed(&self, m: &Mat4) -> Self {
+        let mut min = point3(Float::INFINITY, Float::INFINITY, Float::INFINITY);
+        let mut max = point3(-Float::INFINITY, -Float::INFINITY, -Float::INFINITY);
+
+        for i in 0..8 {
+            let corner = point3(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            let p = m.transform_point(corner);
+
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+
+        aabb(min, max)
+    }
 }
 
 #[inline]
 pub fn aabb(a: Point3, b: Point3) -> AABB {
     AABB::new(a, b)
 }
+
+// Four boxes stored axis by axis, so one ray can be tested against all of
+// them at once.  Unused lanes hold an inside out box that no ray hits.
+#[derive(Copy, Clone, Debug)]
+#[repr(C, align(16))]
+pub struct AABB4 {
+    min: [[Float; 4]; 3],
+    max: [[Float; 4]; 3],
+}
+
+impl AABB4 {
+    pub fn new(boxes: &[AABB]) -> Self {
+        assert!(boxes.len() <= 4, "AABB4 holds at most four boxes");
+
+        let mut min = [[Float::INFINITY; 4]; 3];
+        let mut max = [[-Float::INFINITY; 4]; 3];
+
+        for (lane, bbox) in boxes.iter().enumerate() {
+            for a in 0..3 {
+                min[a][lane] = bbox.min[a];
+                max[a][lane] = bbox.max[a];
+            }
+        }
+
+        Self { min, max }
+    }
+
+    // The boxes hit within [t_min, t_max], as a bit mask, and the t at which
+    // the ray enters each one.  `inv_dir` is 1 / r.direction.
+    #[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+    #[inline]
+    pub fn hit(
+        &self,
+        origin: &Point3,
+        inv_dir: &Vec3,
+        t_min: Float,
+        t_max: Float,
+    ) -> (u32, [Float; 4]) {
+        use std::arch::x86_64::*;
+
+        let mut t = [0.0; 4];
+
+        // SSE is part of the x86_64 baseline, so these are always available,
+        // and the loads and store are unaligned.
+        unsafe {
+            let mut near = _mm_set1_ps(t_min);
+            let mut far = _mm_set1_ps(t_max);
+
+            for a in 0..3 {
+                let (lo, hi) = if inv_dir[a] < 0.0 {
+                    (&self.max[a], &self.min[a])
+                } else {
+                    (&self.min[a], &self.max[a])
+                };
+
+                let o = _mm_set1_ps(origin[a]);
+                let inv = _mm_set1_ps(inv_dir[a]);
+                let t0 = _mm_mul_ps(_mm_sub_ps(_mm_loadu_ps(lo.as_ptr()), o), inv);
+                let t1 = _mm_mul_ps(_mm_sub_ps(_mm_loadu_ps(hi.as_ptr()), o), inv);
+
+                // These return the second operand when the first is NaN,
+                // which happens for a ray in the plane of a slab, so the
+                // slab is ignored as in the scalar test.
+                near = _mm_max_ps(t0, near);
+                far = _mm_min_ps(t1, far);
+            }
+
+            _mm_storeu_ps(t.as_mut_ptr(), near);
+            ((_mm_movemask_ps(_mm_cmplt_ps(near, far)) as u32), t)
+        }
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+    #[inline]
+    pub fn hit(
+        &self,
+        origin: &Point3,
+        inv_dir: &Vec3,
+        t_min: Float,
+        t_max: Float,
+    ) -> (u32, [Float; 4]) {
+        self.hit_scalar(origin, inv_dir, t_min, t_max)
+    }
+
+    // The same test one lane at a time, for other targets and for f64.
+    #[cfg(any(test, not(all(target_arch = "x86_64", not(feature = "f64")))))]
+    fn hit_scalar(
+        &self,
+        origin: &Point3,
+        inv_dir: &Vec3,
+        t_min: Float,
+        t_max: Float,
+    ) -> (u32, [Float; 4]) {
+        let mut mask = 0;
+        let mut t = [0.0; 4];
+
+        for (lane, t) in t.iter_mut().enumerate() {
+            let mut near = t_min;
+            let mut far = t_max;
+
+            for a in 0..3 {
+                let (lo, hi) = if inv_dir[a] < 0.0 {
+                    (self.max[a][lane], self.min[a][lane])
+                } else {
+                    (self.min[a][lane], self.max[a][lane])
+                };
+
+                // Float::max and min also ignore a NaN operand.
+                near = near.max((lo - origin[a]) * inv_dir[a]);
+                far = far.min((hi - origin[a]) * inv_dir[a]);
+            }
+
+            if near < far {
+                mask |= 1 << lane;
+            }
+            *t = near;
+        }
+
+        (mask, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aabb4_matches_single_box_tests() {
+        let boxes = [
+            aabb(point3(-1.0, -1.0, -1.0), point3(1.0, 1.0, 1.0)),
+            aabb(point3(2.0, 0.0, 0.0), point3(3.0, 1.0, 1.0)),
+            aabb(point3(-5.0, -5.0, 4.0), point3(5.0, 5.0, 4.5)),
+        ];
+        let bounds = AABB4::new(&boxes);
+
+        for i in 0..200 {
+            let f = i as Float;
+            let mut direction = vec3((f * 0.7).sin(), (f * 1.3).cos(), (f * 0.3).sin());
+            // Some rays parallel to an axis, starting on a slab boundary.
+            if i % 5 == 0 {
+                direction[i % 3] = 0.0;
+            }
+            let origin = point3((f * 0.9).cos() * 4.0, (f * 0.4).sin(), 1.0);
+            let r = Ray::new(origin, direction, 0.0);
+            let inv_dir = vec3(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+
+            let (mask, t) = bounds.hit(&origin, &inv_dir, 0.001, 100.0);
+            assert_eq!(
+                (mask, t),
+                bounds.hit_scalar(&origin, &inv_dir, 0.001, 100.0)
+            );
+
+            for (lane, bbox) in boxes.iter().enumerate() {
+                let expected = bbox.hit_with_inverse(&r, &inv_dir, 0.001, 100.0);
+                assert_eq!(mask & (1 << lane) != 0, expected);
+                if expected {
+                    assert_eq!(Some(t[lane]), bbox.clip(&r, 0.001, 100.0).map(|s| s.0));
+                }
+            }
+
+            // The unused lane is never hit.
+            assert_eq!(mask & 8, 0);
+        }
+    }
+}
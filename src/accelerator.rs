@@ -0,0 +1,282 @@
+use crate::aabb::*;
+use crate::bvh4::*;
+use crate::bvh_node::*;
+use crate::grid::*;
+use crate::hittable::*;
+use crate::kd_tree::*;
+use crate::vec3::*;
+
+#[cfg(feature = "stats")]
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+// A structure that speeds up finding the nearest hit among many objects.
+// Each one keeps statistics about how it was built and, with the `stats`
+// feature, how it has been traversed, to compare them on a given scene.
+pub trait Accelerator: Hittable {
+    fn name(&self) -> &'static str;
+    fn stats(&self) -> AcceleratorStats;
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AcceleratorStats {
+    pub build_time: Duration,
+    // Bytes used by the structure itself, not counting the objects.
+    pub memory: usize,
+    // Tree nodes, or grid cells.
+    pub nodes: usize,
+    pub rays: u64,
+    pub node_visits: u64,
+    pub primitive_tests: u64,
+}
+
+impl fmt::Display for AcceleratorStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "build {:.2?}, {:.1} KiB, {} nodes",
+            self.build_time,
+            self.memory as f64 / 1024.0,
+            self.nodes,
+        )?;
+
+        // Nothing is counted without the `stats` feature.
+        if self.rays == 0 {
+            return Ok(());
+        }
+
+        let per_ray = |n: u64| n as f64 / self.rays as f64;
+
+        write!(
+            f,
+            ", {} rays, {:.1} nodes/ray, {:.1} tests/ray",
+            self.rays,
+            per_ray(self.node_visits),
+            per_ray(self.primitive_tests),
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AcceleratorKind {
+    #[default]
+    Bvh,
+    Bvh4,
+    Grid,
+    KdTree,
+}
+
+impl FromStr for AcceleratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bvh" => Ok(Self::Bvh),
+            "bvh4" => Ok(Self::Bvh4),
+            "grid" => Ok(Self::Grid),
+            "kdtree" => Ok(Self::KdTree),
+            _ => Err(format!(
+                "Unknown accelerator '{}', expected bvh, bvh4, grid or kdtree",
+                s
+            )),
+        }
+    }
+}
+
+pub fn build_accelerator(
+    kind: AcceleratorKind,
+    objects: &[HittableInstance],
+    time0: Float,
+    time1: Float,
+) -> Box<dyn Accelerator> {
+    match kind {
+        AcceleratorKind::Bvh => Box::new(BVHNode::new(objects, time0, time1)),
+        AcceleratorKind::Bvh4 => Box::new(BVH4::new(objects, time0, time1)),
+        AcceleratorKind::Grid => Box::new(UniformGrid::new(objects, time0, time1)),
+        AcceleratorKind::KdTree => Box::new(KdTree::new(objects, time0, time1)),
+    }
+}
+
+// Split objects into those with a bounding box, along with it, and those
+// without, which every accelerator tests on every ray.
+pub(crate) fn split_bounded(
+    objects: &[HittableInstance],
+    time0: Float,
+    time1: Float,
+) -> (Vec<(HittableInstance, AABB)>, Vec<HittableInstance>) {
+    let mut bounded = Vec::with_capacity(objects.len());
+    let mut unbounded = Vec::new();
+
+    for object in objects {
+        match object.bounding_box(time0, time1) {
+            Some(bbox) => bounded.push((object.clone(), bbox)),
+            None => unbounded.push(object.clone()),
+        }
+    }
+
+    (bounded, unbounded)
+}
+
+// Traversal counts, updated through a shared reference while hit() runs.
+// Counting sits in the innermost traversal loops, so it is only compiled in
+// with the `stats` feature.  Without it the counters are empty and every
+// count stays zero.
+#[derive(Debug, Default)]
+pub(crate) struct TraversalCounters {
+    #[cfg(feature = "stats")]
+    rays: Cell<u64>,
+    #[cfg(feature = "stats")]
+    node_visits: Cell<u64>,
+    #[cfg(feature = "stats")]
+    primitive_tests: Cell<u64>,
+}
+
+#[cfg(feature = "stats")]
+impl TraversalCounters {
+    #[inline]
+    pub(crate) fn ray(&self) {
+        self.rays.set(self.rays.get() + 1);
+    }
+
+    #[inline]
+    pub(crate) fn node(&self) {
+        self.node_visits.set(self.node_visits.get() + 1);
+    }
+
+    #[inline]
+    pub(crate) fn primitives(&self, count: usize) {
+        self.primitive_tests
+            .set(self.primitive_tests.get() + count as u64);
+    }
+
+    // Fill in the traversal part of the statistics.
+    pub(crate) fn stats(
+        &self,
+        build_time: Duration,
+        memory: usize,
+        nodes: usize,
+    ) -> AcceleratorStats {
+        AcceleratorStats {
+            build_time,
+            memory,
+            nodes,
+            rays: self.rays.get(),
+            node_visits: self.node_visits.get(),
+            primitive_tests: self.primitive_tests.get(),
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl TraversalCounters {
+    #[inline(always)]
+    pub(crate) fn ray(&self) {}
+
+    #[inline(always)]
+    pub(crate) fn node(&self) {}
+
+    #[inline(always)]
+    pub(crate) fn primitives(&self, _count: usize) {}
+
+    pub(crate) fn stats(
+        &self,
+        build_time: Duration,
+        memory: usize,
+        nodes: usize,
+    ) -> AcceleratorStats {
+        AcceleratorStats {
+            build_time,
+            memory,
+            nodes,
+            ..AcceleratorStats::default()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hittable_list::*;
+    use crate::material::*;
+    use crate::plane::*;
+    use crate::ray::*;
+    use crate::sphere::*;
+
+    use std::rc::Rc;
+
+    // A clumpy, uneven layout of spheres of different sizes, one large
+    // sphere overlapping many of the others and a ground plane.
+    pub(crate) fn clumpy_objects() -> Vec<HittableInstance> {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        let mut objects: Vec<HittableInstance> = (0..200)
+            .map(|i| {
+                let f = i as Float;
+                let center = point3(
+                    (f * 0.37).sin() * 10.0 + if i % 3 == 0 { 30.0 } else { 0.0 },
+                    (f * 0.73).cos() * 5.0,
+                    (f * 1.31).sin() * 8.0,
+                );
+                let radius = 0.2 + (f * 0.11).cos().abs();
+                Rc::new(Sphere::new(center, radius, material.clone())) as HittableInstance
+            })
+            .collect();
+        objects.push(Rc::new(Sphere::new(
+            point3(5.0, 0.0, 0.0),
+            6.0,
+            material.clone(),
+        )));
+        objects.push(Rc::new(InfinitePlane::new(
+            point3(0.0, -6.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            material,
+        )));
+
+        objects
+    }
+
+    // Check that `accelerator`, built over `objects`, finds the same closest
+    // hits and occlusion as testing every object in turn.
+    pub(crate) fn assert_matches_a_plain_list(
+        accelerator: &dyn Accelerator,
+        objects: &[HittableInstance],
+    ) {
+        let list = HittableList {
+            objects: objects.to_vec(),
+        };
+        #[cfg(feature = "stats")]
+        let before = accelerator.stats();
+
+        for i in 0..500 {
+            let f = i as Float;
+            let r = Ray::new(
+                point3(-40.0, (f * 0.9).sin() * 6.0, (f * 0.3).cos() * 9.0),
+                vec3(1.0, (f * 1.7).sin() * 0.3, (f * 0.5).sin() * 0.2),
+                0.0,
+            );
+
+            let expected = list.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+            let actual = accelerator.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+            assert_eq!(expected, actual, "{}", accelerator.name());
+
+            // And with the far end cut short, as for a shadow ray.
+            let t_max = 20.0 + f % 30.0;
+            let expected = list.hit(&r, 0.001, t_max).is_some();
+            assert_eq!(
+                expected,
+                accelerator.occluded(&r, 0.001, t_max),
+                "{}",
+                accelerator.name()
+            );
+        }
+
+        #[cfg(feature = "stats")]
+        {
+            let stats = accelerator.stats();
+            assert_eq!(stats.rays - before.rays, 1000);
+            assert!(stats.primitive_tests - before.primitive_tests < 1000 * objects.len() as u64);
+        }
+    }
+}
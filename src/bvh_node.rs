@@ -1,118 +1,735 @@
 use crate::aabb::*;
+use crate::accelerator::*;
 use crate::hittable::*;
 use crate::ray::*;
+use crate::vec3::*;
 
-use rand::Rng;
-use std::cmp::Ordering;
-use std::rc::Rc;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
 
+// Relative costs of visiting a node and of intersecting an object, for the
+// surface area heuristic.
+const TRAVERSAL_COST: Float = 0.125;
+const INTERSECTION_COST: Float = 1.0;
+
+const BINS: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+
+// The deepest the tree gets.  Past MAX_SAH_DEPTH the builder stops using the
+// surface area heuristic and halves the objects at their median, which
+// reaches single objects within 32 more levels for any number of them that
+// fits in the u32 offsets.
+pub(crate) const MAX_DEPTH: usize = 64;
+const MAX_SAH_DEPTH: usize = MAX_DEPTH - 32;
+
+// Traversal pushes at most one node per level of the tree.
+const STACK_SIZE: usize = MAX_DEPTH;
+
+// How much a node may grow while refitting before its subtree is rebuilt.
+const REBUILD_GROWTH: Float = 2.0;
+
+// A bounded object with its box, while building.
+type Entry = (HittableInstance, AABB);
+
+// A bounding volume hierarchy, flattened into an array of nodes in depth
+// first order.  The first child of an interior node is the next node in the
+// array, so only the second child needs an index.
 pub struct BVHNode {
-    left: HittableInstance,
-    right: HittableInstance,
-    aabb_box: AABB,
+    pub(crate) nodes: Vec<LinearNode>,
+    // The surface area of each node when it was built, to tell when a
+    // refitted subtree has degraded.
+    built_areas: Vec<Float>,
+    pub(crate) primitives: Vec<HittableInstance>,
+    // Objects without a bounding box, such as infinite planes, are kept out
+    // of the tree and tested on every ray.
+    pub(crate) unbounded: Vec<HittableInstance>,
+    build_time: Duration,
+    counters: TraversalCounters,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LinearNode {
+    pub(crate) bbox: AABB,
+    // The first primitive of a leaf, or the second child of an interior
+    // node.
+    pub(crate) offset: u32,
+    // The number of primitives in a leaf, zero for an interior node.
+    pub(crate) count: u16,
+    // The axis an interior node was split along.
+    pub(crate) axis: u8,
 }
 
 impl BVHNode {
-    pub fn new(objects: &[HittableInstance], time0: f32, time1: f32) -> Self {
-        // Randomly select which axis to partition on.
-        let axis: u32 = rand::thread_rng().gen_range(0..=2);
-        let comparator = match axis {
-            0 => box_x_compare,
-            1 => box_y_compare,
-            _ => box_z_compare,
+    // Builds the tree with a binned surface area heuristic, so the same
+    // objects always give the same tree.
+    pub fn new(objects: &[HittableInstance], time0: Float, time1: Float) -> Self {
+        let start = Instant::now();
+        let (bounded, unbounded) = split_bounded(objects, time0, time1);
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * bounded.len()),
+            built_areas: Vec::with_capacity(2 * bounded.len()),
+            primitives: Vec::with_capacity(bounded.len()),
+            unbounded,
+            build_time: Duration::ZERO,
+            counters: TraversalCounters::default(),
         };
 
-        let span = objects.len();
+        if !bounded.is_empty() {
+            bvh.build(bounded, 0);
+        }
 
-        let (left, right): (HittableInstance, HittableInstance) = match span {
-            1 => (objects[0].clone(), objects[0].clone()),
-            2 => {
-                if comparator(&objects[0], &objects[1]) == Ordering::Less {
-                    (objects[0].clone(), objects[1].clone())
-                } else {
-                    (objects[1].clone(), objects[0].clone())
-                }
-            }
-            _ => {
-                let mut cloned: Vec<HittableInstance> = objects.to_vec();
-                cloned.sort_by(comparator);
+        bvh.build_time = start.elapsed();
+        bvh
+    }
 
-                let mid = cloned.len() / 2;
+    // Appends the subtree for `objects`, whose root is `depth` levels down
+    // the whole tree, and returns the index of its root.
+    fn build(&mut self, objects: Vec<Entry>, depth: usize) -> usize {
+        let bbox = surrounding(&objects);
+        let index = self.nodes.len();
+        self.built_areas.push(bbox.surface_area());
 
-                (
-                    Rc::new(Self::new(&cloned[0..mid], time0, time1)),
-                    Rc::new(Self::new(&cloned[mid..], time0, time1)),
-                )
-            }
+        let split = if depth < MAX_SAH_DEPTH {
+            best_split(&objects, &bbox)
+        } else {
+            None
         };
+        let leaf_cost = objects.len() as Float * INTERSECTION_COST;
+        let split_cost = split.map_or(Float::INFINITY, |s| s.2);
+
+        if objects.len() == 1 || (objects.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost) {
+            self.nodes.push(LinearNode {
+                bbox,
+                offset: self.primitives.len() as u32,
+                count: objects.len() as u16,
+                axis: 0,
+            });
+            self.primitives
+                .extend(objects.into_iter().map(|(object, _)| object));
+
+            return index;
+        }
+
+        let (axis, (left, right)) = match split {
+            Some((axis, bin, _)) => (axis, partition(objects, axis, bin)),
+            // Too deep, or every centroid is in the same place.
+            None => median_split(objects),
+        };
+
+        self.nodes.push(LinearNode {
+            bbox,
+            offset: 0,
+            count: 0,
+            axis: axis as u8,
+        });
+
+        self.build(left, depth + 1);
+        let second = self.build(right, depth + 1);
+        self.nodes[index].offset = second as u32;
 
-        let box_left = left.bounding_box(time0, time1);
-        let box_right = right.bounding_box(time0, time1);
+        index
+    }
+
+    // Recompute the bounds of every node for the interval [time0, time1],
+    // keeping the structure of the tree.  This is much cheaper than building
+    // a new tree when the objects have moved a little, such as animated
+    // objects from one frame to the next.
+    pub fn refit(&mut self, time0: Float, time1: Float) {
+        // Children always come after their parent, so a backward pass sees
+        // them first.
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            let first = node.offset as usize;
+
+            self.nodes[index].bbox = if node.count > 0 {
+                self.primitives[first..first + node.count as usize]
+                    .iter()
+                    .map(|object| primitive_box(object, time0, time1))
+                    .reduce(|a, b| AABB::surrounding_box(&a, &b))
+                    .unwrap()
+            } else {
+                AABB::surrounding_box(&self.nodes[index + 1].bbox, &self.nodes[first].bbox)
+            };
+        }
+    }
+
+    // Refit for [time0, time1], then rebuild the subtrees that have grown
+    // too much since they were built, as the objects in them have moved
+    // apart.
+    pub fn update(&mut self, time0: Float, time1: Float) {
+        self.refit(time0, time1);
+
+        if !self.nodes.is_empty() {
+            self.rebuild_degraded(0, 0, time0, time1);
+        }
+    }
+
+    fn rebuild_degraded(&mut self, index: usize, depth: usize, time0: Float, time1: Float) {
+        let node = self.nodes[index];
+        if node.count > 0 {
+            return;
+        }
 
-        if box_left.is_none() || box_right.is_none() {
-            panic!("No bounding box in BVHNode constructor.");
+        if node.bbox.surface_area() > REBUILD_GROWTH * self.built_areas[index] {
+            self.rebuild(index, depth, time0, time1);
+            return;
         }
 
-        let aabb_box = AABB::surrounding_box(&box_left.unwrap(), &box_right.unwrap());
+        self.rebuild_degraded(index + 1, depth + 1, time0, time1);
+        // Rebuilding the first child can move the second one.
+        let second = self.nodes[index].offset as usize;
+        self.rebuild_degraded(second, depth + 1, time0, time1);
+    }
+
+    // Replace the subtree at `index`, `depth` levels down, with a new one
+    // built from the same primitives.
+    fn rebuild(&mut self, index: usize, depth: usize, time0: Float, time1: Float) {
+        let end = self.subtree_end(index);
+        let (first, count) = self.primitive_range(index, end);
+
+        let entries: Vec<Entry> = self.primitives[first..first + count]
+            .iter()
+            .map(|object| (object.clone(), primitive_box(object, time0, time1)))
+            .collect();
 
-        Self {
-            left,
-            right,
-            aabb_box,
+        let mut subtree = Self {
+            nodes: Vec::new(),
+            built_areas: Vec::new(),
+            primitives: Vec::with_capacity(count),
+            unbounded: Vec::new(),
+            build_time: Duration::ZERO,
+            counters: TraversalCounters::default(),
+        };
+        subtree.build(entries, depth);
+
+        // Rebase the new nodes onto where they go in this tree.
+        for node in subtree.nodes.iter_mut() {
+            node.offset += if node.count > 0 { first } else { index } as u32;
         }
+
+        // The primitives keep the same range, only their order changes, but
+        // the subtree may now have a different number of nodes.
+        let new_end = index + subtree.nodes.len();
+        for (i, node) in self.nodes.iter_mut().enumerate() {
+            if (i < index || i >= end) && node.count == 0 && node.offset as usize >= end {
+                node.offset = (node.offset as usize - end + new_end) as u32;
+            }
+        }
+
+        self.primitives
+            .splice(first..first + count, subtree.primitives);
+        self.nodes.splice(index..end, subtree.nodes);
+        self.built_areas.splice(index..end, subtree.built_areas);
+    }
+
+    // The index just past the last node of the subtree at `index`.
+    fn subtree_end(&self, index: usize) -> usize {
+        let mut index = index;
+
+        // The last node is the end of the chain of second children.
+        while self.nodes[index].count == 0 {
+            index = self.nodes[index].offset as usize;
+        }
+
+        index + 1
+    }
+
+    // The first primitive and number of primitives in the leaves of the
+    // nodes from `index` to `end`, which are contiguous.
+    fn primitive_range(&self, index: usize, end: usize) -> (usize, usize) {
+        let leaves = self.nodes[index..end].iter().filter(|node| node.count > 0);
+
+        let first = leaves
+            .clone()
+            .map(|node| node.offset as usize)
+            .min()
+            .unwrap();
+        let count = leaves.map(|node| node.count as usize).sum();
+
+        (first, count)
     }
 }
 
+#[inline]
+fn primitive_box(object: &HittableInstance, time0: Float, time1: Float) -> AABB {
+    object
+        .bounding_box(time0, time1)
+        .expect("Objects in a BVHNode must stay bounded")
+}
+
 impl Hittable for BVHNode {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        if !self.aabb_box.hit(r, t_min, t_max) {
-            return None;
-        }
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.counters.ray();
+        self.counters.primitives(self.unbounded.len());
 
         let mut rec: Option<HitRecord> = None;
         let mut closest_t = t_max;
 
-        for object in [&self.left, &self.right] {
+        for object in &self.unbounded {
             if let Some(r) = object.hit(r, t_min, closest_t) {
                 closest_t = r.t;
                 rec = Some(r);
             }
         }
 
+        if self.nodes.is_empty() {
+            return rec;
+        }
+
+        let inv_dir = vec3(
+            1.0 / r.direction.x,
+            1.0 / r.direction.y,
+            1.0 / r.direction.z,
+        );
+        let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            self.counters.node();
+
+            if node.bbox.hit_with_inverse(r, &inv_dir, t_min, closest_t) {
+                if node.count > 0 {
+                    let first = node.offset as usize;
+                    self.counters.primitives(node.count as usize);
+
+                    for object in &self.primitives[first..first + node.count as usize] {
+                        if let Some(r) = object.hit(r, t_min, closest_t) {
+                            closest_t = r.t;
+                            rec = Some(r);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer the ray origin first, so that
+                    // later boxes can be culled by the closer hit.
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset as usize, current + 1)
+                    } else {
+                        (current + 1, node.offset as usize)
+                    };
+
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
         rec
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
-        Some(self.aabb_box)
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.counters.ray();
+        self.counters.primitives(self.unbounded.len());
+
+        if self
+            .unbounded
+            .iter()
+            .any(|object| object.occluded(r, t_min, t_max))
+        {
+            return true;
+        }
+
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_dir = vec3(
+            1.0 / r.direction.x,
+            1.0 / r.direction.y,
+            1.0 / r.direction.z,
+        );
+
+        // Any hit will do, so the order of the children does not matter.
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            self.counters.node();
+
+            if node.bbox.hit_with_inverse(r, &inv_dir, t_min, t_max) {
+                if node.count > 0 {
+                    let first = node.offset as usize;
+                    self.counters.primitives(node.count as usize);
+
+                    if self.primitives[first..first + node.count as usize]
+                        .iter()
+                        .any(|object| object.occluded(r, t_min, t_max))
+                    {
+                        return true;
+                    }
+                } else {
+                    stack[stack_len] = node.offset as usize;
+                    stack_len += 1;
+                    current += 1;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                return false;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        match self.nodes.first() {
+            Some(root) => Some(root.bbox),
+            None => {
+                // An inside out box that no ray hits.
+                let inf = Float::INFINITY;
+                Some(aabb(point3(inf, inf, inf), point3(-inf, -inf, -inf)))
+            }
+        }
     }
 }
 
-#[inline]
-fn box_x_compare(a: &HittableInstance, b: &HittableInstance) -> Ordering {
-    boxes(a, b, 0)
+impl Accelerator for BVHNode {
+    fn name(&self) -> &'static str {
+        "bvh"
+    }
+
+    fn stats(&self) -> AcceleratorStats {
+        let memory = self.nodes.len() * size_of::<LinearNode>()
+            + self.built_areas.len() * size_of::<Float>()
+            + (self.primitives.len() + self.unbounded.len()) * size_of::<HittableInstance>();
+
+        self.counters
+            .stats(self.build_time, memory, self.nodes.len())
+    }
+}
+
+fn surrounding(objects: &[Entry]) -> AABB {
+    objects[1..]
+        .iter()
+        .fold(objects[0].1, |bbox, (_, b)| AABB::surrounding_box(&bbox, b))
 }
 
 #[inline]
-fn box_y_compare(a: &HittableInstance, b: &HittableInstance) -> Ordering {
-    boxes(a, b, 1)
+fn centroid(bbox: &AABB) -> Point3 {
+    0.5 * (bbox.min + bbox.max)
 }
 
 #[inline]
-fn box_z_compare(a: &HittableInstance, b: &HittableInstance) -> Ordering {
-    boxes(a, b, 2)
+fn bin_of(c: Float, low: Float, extent: Float) -> usize {
+    (((c - low) / extent * BINS as Float) as usize).min(BINS - 1)
+}
+
+// The axis and bin to split after with the lowest estimated cost, along with
+// that cost.  None when the centroids cannot be told apart.
+fn best_split(objects: &[Entry], bbox: &AABB) -> Option<(usize, usize, Float)> {
+    let centroids: Vec<Point3> = objects.iter().map(|(_, b)| centroid(b)).collect();
+    let mut low = centroids[0];
+    let mut high = centroids[0];
+    for c in &centroids[1..] {
+        for a in 0..3 {
+            low[a] = low[a].min(c[a]);
+            high[a] = high[a].max(c[a]);
+        }
+    }
+
+    let parent_area = bbox.surface_area();
+    let mut best: Option<(usize, usize, Float)> = None;
+
+    for axis in 0..3 {
+        let extent = high[axis] - low[axis];
+        if extent <= 0.0 {
+            continue;
+        }
+
+        let mut counts = [0usize; BINS];
+        let mut boxes: [Option<AABB>; BINS] = [None; BINS];
+
+        for ((_, b), c) in objects.iter().zip(&centroids) {
+            let bin = bin_of(c[axis], low[axis], extent);
+            counts[bin] += 1;
+            boxes[bin] = Some(boxes[bin].map_or(*b, |bb| AABB::surrounding_box(&bb, b)));
+        }
+
+        // Sweep from the right to get the area and count of each right side,
+        // then from the left to cost each split.
+        let mut right_area = [0.0; BINS];
+        let mut right_count = [0usize; BINS];
+        let mut acc: Option<AABB> = None;
+        let mut n = 0;
+        for bin in (1..BINS).rev() {
+            if let Some(b) = boxes[bin] {
+                acc = Some(acc.map_or(b, |a| AABB::surrounding_box(&a, &b)));
+            }
+            n += counts[bin];
+            right_area[bin] = acc.map_or(0.0, |a| a.surface_area());
+            right_count[bin] = n;
+        }
+
+        let mut acc: Option<AABB> = None;
+        let mut n = 0;
+        for split in 0..BINS - 1 {
+            if let Some(b) = boxes[split] {
+                acc = Some(acc.map_or(b, |a| AABB::surrounding_box(&a, &b)));
+            }
+            n += counts[split];
+
+            let right = right_count[split + 1];
+            if n == 0 || right == 0 {
+                continue;
+            }
+
+            let left_area = acc.map_or(0.0, |a| a.surface_area());
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (left_area * n as Float + right_area[split + 1] * right as Float)
+                    / parent_area;
+
+            if best.is_none_or(|b| cost < b.2) {
+                best = Some((axis, split, cost));
+            }
+        }
+    }
+
+    best
+}
+
+// Split the objects by which side of the bin boundary their centroids fall.
+fn partition(objects: Vec<Entry>, axis: usize, split: usize) -> (Vec<Entry>, Vec<Entry>) {
+    let mut low = Float::INFINITY;
+    let mut high = -Float::INFINITY;
+    for (_, b) in &objects {
+        let c = centroid(b)[axis];
+        low = low.min(c);
+        high = high.max(c);
+    }
+
+    objects
+        .into_iter()
+        .partition(|(_, b)| bin_of(centroid(b)[axis], low, high - low) <= split)
+}
+
+// Halve the objects at the median of their centroids along the axis they
+// spread furthest along.
+fn median_split(mut objects: Vec<Entry>) -> (usize, (Vec<Entry>, Vec<Entry>)) {
+    let mut low = centroid(&objects[0].1);
+    let mut high = low;
+    for (_, b) in &objects[1..] {
+        let c = centroid(b);
+        for a in 0..3 {
+            low[a] = low[a].min(c[a]);
+            high[a] = high[a].max(c[a]);
+        }
+    }
+    let extent = high - low;
+
+    let axis = (0..3)
+        .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+        .unwrap();
+    objects.sort_by(|(_, a), (_, b)| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+
+    let right = objects.split_off(objects.len() / 2);
+    (axis, (objects, right))
 }
 
-fn boxes(a: &HittableInstance, b: &HittableInstance, axis: usize) -> Ordering {
-    let box_a = a.bounding_box(0.0, 0.0);
-    let box_b = b.bounding_box(0.0, 0.0);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accelerator::tests::*;
+    use crate::hittable_list::*;
+    use crate::material::*;
+    use crate::plane::*;
+    use crate::quat::*;
+    use crate::sphere::*;
+
+    use std::rc::Rc;
+
+    // Check that every subtree is a contiguous run of nodes starting at its
+    // root, that each node holds its children, and that the leaves cover
+    // each primitive once, in order.  Returns the depth of the tree.
+    fn assert_depth_first(bvh: &BVHNode) -> usize {
+        fn check(bvh: &BVHNode, index: usize, next_primitive: &mut usize) -> (usize, usize) {
+            let node = bvh.nodes[index];
+            if node.count > 0 {
+                assert_eq!(node.offset as usize, *next_primitive);
+                *next_primitive += node.count as usize;
+                return (index + 1, 1);
+            }
+
+            let (first_end, first_depth) = check(bvh, index + 1, next_primitive);
+            assert_eq!(node.offset as usize, first_end);
+            let (end, second_depth) = check(bvh, first_end, next_primitive);
+
+            for child in [index + 1, first_end] {
+                let b = bvh.nodes[child].bbox;
+                for a in 0..3 {
+                    assert!(node.bbox.min[a] <= b.min[a] && b.max[a] <= node.bbox.max[a]);
+                }
+            }
+
+            (end, 1 + first_depth.max(second_depth))
+        }
+
+        let mut next_primitive = 0;
+        let (end, depth) = check(bvh, 0, &mut next_primitive);
+        assert_eq!(end, bvh.nodes.len());
+        assert_eq!(next_primitive, bvh.primitives.len());
+        assert_eq!(bvh.built_areas.len(), bvh.nodes.len());
+
+        depth
+    }
+
+    #[test]
+    fn lays_out_nodes_depth_first() {
+        let objects = clumpy_objects();
+        let bvh = BVHNode::new(&objects, 0.0, 1.0);
 
-    if box_a.is_none() || box_b.is_none() {
-        panic!("No bounding box in BVHNode constructor.");
+        assert_depth_first(&bvh);
+        assert_eq!(bvh.primitives.len() + bvh.unbounded.len(), objects.len());
     }
 
-    if box_a.unwrap().min[axis] < box_b.unwrap().min[axis] {
-        Ordering::Less
-    } else {
-        Ordering::Greater
+    #[test]
+    fn stays_shallow_enough_to_traverse() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        // Each sphere half the size and distance of the last, so the surface
+        // area heuristic peels off a few of them at each level.
+        let objects: Vec<HittableInstance> = (0..120)
+            .map(|i| {
+                let x = (0.5 as Float).powi(i);
+                Rc::new(Sphere::new(point3(x, 0.0, 0.0), 0.25 * x, material.clone()))
+                    as HittableInstance
+            })
+            .collect();
+
+        // Deep down a larger tree, they are split at the median instead.
+        let mut subtree = BVHNode::new(&[], 0.0, 1.0);
+        let entries = split_bounded(&objects, 0.0, 1.0).0;
+        subtree.build(entries, MAX_SAH_DEPTH);
+        assert!(MAX_SAH_DEPTH + assert_depth_first(&subtree) <= MAX_DEPTH);
+
+        // Through every box down to the smallest sphere.
+        let bvh = BVHNode::new(&objects, 0.0, 1.0);
+        let list = HittableList { objects };
+        for r in [
+            Ray::new(point3(-1.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0),
+            Ray::new(point3(2.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0),
+        ] {
+            let expected = list.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+            let actual = bvh.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+            assert_eq!(expected, actual);
+            assert!(bvh.occluded(&r, 0.001, Float::INFINITY));
+        }
+    }
+
+    #[test]
+    fn keeps_unbounded_objects_out_of_the_tree() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let objects: Vec<HittableInstance> = vec![
+            Rc::new(InfinitePlane::new(
+                point3(0.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                material.clone(),
+            )),
+            Rc::new(Sphere::new(point3(0.0, 1.0, 0.0), 1.0, material.clone())),
+            Rc::new(Sphere::new(point3(5.0, 1.0, 0.0), 1.0, material)),
+        ];
+
+        let bvh = BVHNode::new(&objects, 0.0, 1.0);
+        assert!(bvh.bounding_box(0.0, 1.0).is_none());
+
+        // Far from the spheres, only the plane is there.
+        let r = Ray::new(point3(100.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(bvh.hit(&r, 0.001, Float::INFINITY).unwrap().t, 5.0);
+
+        // The sphere on top of the plane is nearer.
+        let r = Ray::new(point3(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(bvh.hit(&r, 0.001, Float::INFINITY).unwrap().t, 3.0);
+
+        // With nothing bounded, the tree is empty but still works.
+        let bvh = BVHNode::new(&objects[..1], 0.0, 1.0);
+        assert_eq!(bvh.hit(&r, 0.001, Float::INFINITY).unwrap().t, 5.0);
+    }
+
+    #[test]
+    fn matches_a_plain_list() {
+        let objects = clumpy_objects();
+        let accelerator = BVHNode::new(&objects, 0.0, 1.0);
+
+        assert_matches_a_plain_list(&accelerator, &objects);
+    }
+
+    #[test]
+    fn refits_and_rebuilds_for_later_frames() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        // A still, spread out half, and a packed half that flies apart.
+        let objects: Vec<HittableInstance> = (0..100)
+            .map(|i| {
+                let f = i as Float;
+                let dir = vec3((f * 0.37).sin(), (f * 0.73).cos(), (f * 1.31).sin());
+                let (start, end) = if i < 50 {
+                    (15.0 * dir, 15.0 * dir)
+                } else {
+                    (dir, 8.0 * dir)
+                };
+
+                let sphere = Rc::new(Sphere::new(point3(0.0, 0.0, 0.0), 0.2, material.clone()));
+                let keyframes = vec![
+                    Keyframe::new(0.0, start, Quat::IDENTITY, vec3(1.0, 1.0, 1.0)),
+                    Keyframe::new(10.0, end, Quat::IDENTITY, vec3(1.0, 1.0, 1.0)),
+                ];
+                Rc::new(AnimatedTransform::new(sphere, keyframes)) as HittableInstance
+            })
+            .collect();
+
+        let mut bvh = BVHNode::new(&objects, 0.0, 0.0);
+        let list = HittableList { objects };
+
+        // Against a plain list, and a tree built from scratch at that time.
+        let check = |bvh: &BVHNode, time: Float| {
+            assert_depth_first(bvh);
+            let fresh = BVHNode::new(&list.objects, time, time);
+
+            for i in 0..500 {
+                let f = i as Float;
+                let r = Ray::new(
+                    point3(-40.0, (f * 0.9).sin() * 15.0, (f * 0.3).cos() * 15.0),
+                    vec3(1.0, (f * 1.7).sin() * 0.2, (f * 0.5).sin() * 0.2),
+                    time,
+                );
+
+                let expected = list.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+                let actual = bvh.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+                assert_eq!(expected, actual);
+
+                let rebuilt = fresh.hit(&r, 0.001, Float::INFINITY).map(|rec| rec.t);
+                assert_eq!(rebuilt, actual);
+            }
+        };
+
+        bvh.refit(1.0, 1.0);
+        check(&bvh, 1.0);
+
+        bvh.update(10.0, 10.0);
+        check(&bvh, 10.0);
+
+        // And back, refitting the rebuilt subtrees.
+        bvh.update(0.0, 0.0);
+        check(&bvh, 0.0);
     }
 }
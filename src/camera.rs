@@ -10,24 +10,25 @@ pub struct Camera {
     v: Vec3,
     #[allow(dead_code)]
     w: Vec3,
-    lens_radius: f32,
+    lens_radius: Float,
 
     // shutter open/close times
-    time0: f32,
-    time1: f32,
+    time0: Float,
+    time1: Float,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
         vup: Vec3,
-        vfov: f32,
-        aspect_ratio: f32,
-        aperture: f32,
-        focus_dist: f32,
-        time0: f32,
-        time1: f32,
+        vfov: Float,
+        aspect_ratio: Float,
+        aperture: Float,
+        focus_dist: Float,
+        time0: Float,
+        time1: Float,
     ) -> Camera {
         let theta = vfov.to_radians();
         let h = (theta / 2.0).tan();
@@ -57,7 +58,7 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
+    pub fn get_ray(&self, s: Float, t: Float) -> Ray {
         let rd = self.lens_radius * random_unit_in_disk();
         let offset = self.u * rd.x + self.v * rd.y;
 
@@ -67,6 +68,16 @@ impl Camera {
             super::random_range(self.time0, self.time1),
         )
     }
+
+    // The ray through the middle of the lens at shutter open, so the same
+    // (s, t) always gives the same ray.
+    pub fn center_ray(&self, s: Float, t: Float) -> Ray {
+        Ray::new(
+            self.origin,
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
+            self.time0,
+        )
+    }
 }
 
 fn random_unit_in_disk() -> Vec3 {
@@ -0,0 +1,212 @@
+use crate::aabb::*;
+use crate::cylinder::quadratic_roots;
+use crate::disk::basis;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use crate::vec3::consts::PI;
+use std::rc::Rc;
+
+// All points within `radius` of the segment from `p0` to `p1`: a cylinder
+// closed by two hemispheres.
+pub struct Capsule {
+    p0: Point3,
+    p1: Point3,
+    radius: Float,
+    material: Rc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(p0: Point3, p1: Point3, radius: Float, material: Rc<dyn Material>) -> Self {
+        Self {
+            p0,
+            p1,
+            radius,
+            material,
+        }
+    }
+
+    // With both ends at the same point, the capsule is a sphere: there is no
+    // body, and each cap is the whole sphere.
+    #[inline]
+    fn is_degenerate(&self) -> bool {
+        (self.p1 - self.p0).length_squared() == 0.0
+    }
+
+    // Distance of `p` along the axis, as a fraction of its length.
+    #[inline]
+    fn along(&self, p: Point3) -> Float {
+        if self.is_degenerate() {
+            return 0.0;
+        }
+
+        let axis = self.p1 - self.p0;
+        (p - self.p0).dot(&axis) / axis.length_squared()
+    }
+
+    // The t of the nearest hit.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let axis = self.p1 - self.p0;
+        let axis_length_squared = axis.length_squared();
+
+        let mut closest = t_max;
+        let mut found = false;
+        let mut candidate = |t: Float| {
+            if t >= t_min && t < closest {
+                closest = t;
+                found = true;
+            }
+        };
+
+        // The body: an infinite cylinder, clipped to the segment.
+        if !self.is_degenerate() {
+            let o = r.origin - self.p0;
+            let o_perp = o - (o.dot(&axis) / axis_length_squared) * axis;
+            let d_perp = r.direction - (r.direction.dot(&axis) / axis_length_squared) * axis;
+
+            if let Some((t0, t1)) = quadratic_roots(
+                d_perp.length_squared(),
+                o_perp.dot(&d_perp),
+                o_perp.length_squared() - self.radius * self.radius,
+            ) {
+                for t in [t0, t1] {
+                    if (0.0..=1.0).contains(&self.along(r.at(t))) {
+                        candidate(t);
+                    }
+                }
+            }
+        }
+
+        // The caps: the half of each end sphere beyond the segment.
+        for (center, outside) in [(self.p0, -1.0), (self.p1, 1.0)] {
+            let oc = r.origin - center;
+
+            if let Some((t0, t1)) = quadratic_roots(
+                r.direction.length_squared(),
+                oc.dot(&r.direction),
+                oc.length_squared() - self.radius * self.radius,
+            ) {
+                for t in [t0, t1] {
+                    if outside * (r.at(t) - center).dot(&axis) >= 0.0 {
+                        candidate(t);
+                    }
+                }
+            }
+        }
+
+        found.then_some(closest)
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let t = self.intersect(r, t_min, t_max)?;
+
+        let axis = self.p1 - self.p0;
+        let axis_length_squared = axis.length_squared();
+
+        let s = self.along(r.at(t)).clamp(0.0, 1.0);
+        let center = self.p0 + s * axis;
+        let outward_normal = (r.at(t) - center).unit();
+
+        // Put the point back on the surface, which leaves much less error
+        // than the roots of the quadratics.
+        let offset = self.radius * outward_normal;
+        let p = center + offset;
+        let p_error = gamma(5) * (offset.abs() + self.p0.abs() + (s * axis).abs());
+
+        // A sphere has its uvs around +Y.
+        let axis_length = axis_length_squared.sqrt();
+        let axis_direction = if self.is_degenerate() {
+            vec3(0.0, 1.0, 0.0)
+        } else {
+            axis / axis_length
+        };
+        let (tangent, bitangent) = basis(axis_direction);
+
+        let phi = outward_normal
+            .dot(&bitangent)
+            .atan2(outward_normal.dot(&tangent))
+            + PI;
+        let u = phi / (2.0 * PI);
+        let v =
+            ((p - self.p0).dot(&axis_direction) + self.radius) / (axis_length + 2.0 * self.radius);
+
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        let radius = vec3(self.radius, self.radius, self.radius);
+        let box0 = aabb(self.p0 - radius, self.p0 + radius);
+        let box1 = aabb(self.p1 - radius, self.p1 + radius);
+
+        Some(AABB::surrounding_box(&box0, &box1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_the_body_and_ends() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let capsule = Capsule::new(point3(0.0, 0.0, 0.0), point3(0.0, 2.0, 0.0), 0.5, material);
+
+        let body = Ray::new(point3(5.0, 1.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let rec = capsule.hit(&body, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-5);
+        assert!((rec.normal - vec3(1.0, 0.0, 0.0)).length() < 1e-5);
+
+        // The top end is round, and bulges past the end of the segment.
+        let top = Ray::new(point3(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let rec = capsule.hit(&top, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-5);
+        assert!((rec.normal - vec3(0.0, 1.0, 0.0)).length() < 1e-5);
+        assert!((rec.v - 1.0).abs() < 1e-5);
+
+        let corner = Ray::new(point3(5.0, 2.4, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let rec = capsule.hit(&corner, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.p - point3(0.3, 2.4, 0.0)).length() < 1e-4);
+
+        let over = Ray::new(point3(5.0, 2.6, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        assert!(capsule.hit(&over, 0.0, Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn is_a_sphere_when_both_ends_meet() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let center = point3(1.0, 2.0, 3.0);
+        let capsule = Capsule::new(center, center, 0.5, material);
+
+        for direction in [vec3(0.0, 0.0, -1.0), vec3(1.0, -1.0, 0.0).unit()] {
+            let r = Ray::new(center - 5.0 * direction, direction, 0.0);
+            let rec = capsule.hit(&r, 0.0, Float::INFINITY).unwrap();
+
+            assert!((rec.t - 4.5).abs() < 1e-4);
+            assert!((rec.normal + direction).length() < 1e-4);
+            assert!((0.0..=1.0).contains(&rec.u) && (0.0..=1.0).contains(&rec.v));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::aabb::*;
+use crate::cylinder::{cap_hit, phi_of, quadratic_roots};
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use crate::vec3::consts::PI;
+use std::rc::Rc;
+
+// A cone with its base disk centered on `base` and its apex `height` above
+// it, along the +Y axis.
+pub struct Cone {
+    base: Point3,
+    radius: Float,
+    height: Float,
+    phi_max: Float,
+    capped: bool,
+    material: Rc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(base: Point3, radius: Float, height: Float, material: Rc<dyn Material>) -> Self {
+        Self {
+            base,
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: true,
+            material,
+        }
+    }
+
+    // Leave the base open.
+    pub fn uncapped(self) -> Self {
+        Self {
+            capped: false,
+            ..self
+        }
+    }
+
+    // Only keep the part of the surface from 0 to `angle` degrees around the
+    // axis, measured from +X towards +Z.
+    pub fn with_sweep(self, angle: Float) -> Self {
+        Self {
+            phi_max: angle.clamp(0.0, 360.0).to_radians(),
+            ..self
+        }
+    }
+
+    // The nearest point on the side to a local point near it, which has much
+    // less error than the roots of the quadratic.
+    fn project(&self, p: Vec3) -> Vec3 {
+        let rho = (p.x * p.x + p.z * p.z).sqrt();
+        if rho == 0.0 {
+            return p;
+        }
+
+        // Project onto the slant line from the rim to the apex, in the
+        // plane through the axis and p.
+        let slant = vec3(-self.radius, self.height, 0.0).unit();
+        let along = (rho - self.radius) * slant.x + p.y * slant.y;
+        let rho_on = self.radius + along * slant.x;
+        let y_on = along * slant.y;
+
+        vec3(p.x * rho_on / rho, y_on, p.z * rho_on / rho)
+    }
+
+    // The t of the nearest hit, the outward normal there and its uvs.
+    fn intersect(
+        &self,
+        r: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(Float, Vec3, Float, Float)> {
+        let o = r.origin - self.base;
+        let d = r.direction;
+        let mut closest: Option<(Float, Vec3, Float, Float)> = None;
+
+        // The side: x^2 + z^2 = k^2 (height - y)^2, with k = radius / height
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.y;
+
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let half_b = o.x * d.x + o.z * d.z + k2 * h * d.y;
+        let c = o.x * o.x + o.z * o.z - k2 * h * h;
+
+        for t in quadratic_roots(a, half_b, c)
+            .into_iter()
+            .flat_map(|(t0, t1)| [t0, t1])
+        {
+            if t < t_min || t > t_max {
+                continue;
+            }
+
+            let p = o + t * d;
+            let phi = phi_of(p);
+
+            if p.y >= 0.0 && p.y <= self.height && phi <= self.phi_max {
+                // The normal is undefined at the apex, so point it up the axis.
+                let gradient = vec3(p.x, k2 * (self.height - p.y), p.z);
+                let normal = if gradient.near_zero() {
+                    vec3(0.0, 1.0, 0.0)
+                } else {
+                    gradient.unit()
+                };
+                closest = Some((t, normal, phi / self.phi_max, p.y / self.height));
+                break;
+            }
+        }
+
+        if self.capped {
+            let t_far = closest.map_or(t_max, |c| c.0);
+
+            if let Some((t, u, v)) = cap_hit(o, d, 0.0, self.radius, self.phi_max, t_min, t_far) {
+                closest = Some((t, vec3(0.0, -1.0, 0.0), u, v));
+            }
+        }
+
+        closest
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, outward_normal, u, v) = self.intersect(r, t_min, t_max)?;
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        let (p, p_error) = if outward_normal.y > -1.0 {
+            let local = self.project((r.origin - self.base) + t * r.direction);
+            let p = self.base + local;
+
+            (p, gamma(8) * local.abs() + gamma(1) * p.abs())
+        } else {
+            (r.at(t), ray_point_error(r, t))
+        };
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(aabb(
+            self.base - vec3(self.radius, 0.0, self.radius),
+            self.base + vec3(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cone() -> Cone {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        Cone::new(point3(0.0, 0.0, 0.0), 1.0, 1.0, material)
+    }
+
+    #[test]
+    fn hits_the_side_and_base() {
+        let cone = cone();
+
+        let side = Ray::new(point3(5.0, 0.5, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let rec = cone.hit(&side, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-5);
+        assert!((rec.normal - vec3(1.0, 1.0, 0.0).unit()).length() < 1e-5);
+        assert!((rec.v - 0.5).abs() < 1e-5);
+
+        let base = Ray::new(point3(0.5, -5.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        let rec = cone.hit(&base, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-5);
+        assert_eq!(rec.normal, vec3(0.0, -1.0, 0.0));
+
+        // Above the apex, and up through the open base to the inside.
+        let over = Ray::new(point3(5.0, 1.5, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        assert!(cone.hit(&over, 0.0, Float::INFINITY).is_none());
+
+        let rec = cone
+            .uncapped()
+            .hit(&base, 0.0, Float::INFINITY)
+            .map(|rec| (rec.t, rec.front_face));
+        assert!(matches!(rec, Some((t, false)) if (t - 5.5).abs() < 1e-5));
+    }
+
+    #[test]
+    fn hits_the_side_along_the_slant() {
+        let cone = cone();
+
+        // Parallel to the slant from the apex down to +X, so the quadratic
+        // for the side is linear.
+        let r = Ray::new(point3(-1.0, 1.0, 0.0), vec3(1.0, -1.0, 0.0), 0.0);
+        let rec = cone.hit(&r, 0.0, Float::INFINITY).unwrap();
+
+        assert!((rec.t - 0.5).abs() < 1e-5);
+        assert!((rec.p - point3(-0.5, 0.5, 0.0)).length() < 1e-5);
+        assert!((rec.normal - vec3(-1.0, 1.0, 0.0).unit()).length() < 1e-5);
+        assert!(rec.front_face);
+    }
+}
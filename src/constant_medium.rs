@@ -10,11 +10,11 @@ use std::rc::Rc;
 pub struct ConstantMedium {
     boundary: HittableInstance,
     phase_function: Rc<dyn Material>,
-    neg_inv_density: f32,
+    neg_inv_density: Float,
 }
 
 impl ConstantMedium {
-    pub fn with_texture(b: HittableInstance, d: f32, a: Rc<dyn Texture>) -> Self {
+    pub fn with_texture(b: HittableInstance, d: Float, a: Rc<dyn Texture>) -> Self {
         Self {
             boundary: b,
             neg_inv_density: -1.0 / d,
@@ -22,69 +22,132 @@ impl ConstantMedium {
         }
     }
 
-    pub fn with_color(b: HittableInstance, d: f32, c: Color) -> Self {
+    pub fn with_color(b: HittableInstance, d: Float, c: Color) -> Self {
         Self {
             boundary: b,
             neg_inv_density: -1.0 / d,
             phase_function: Rc::new(Isotropic::with_color(c)),
         }
     }
-}
 
-impl Hittable for ConstantMedium {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let h1 = self.boundary.hit(r, -f32::INFINITY, f32::INFINITY);
-        if h1.is_none() {
-            return None;
-        }
-        let rec1 = h1.unwrap();
+    // The t where the ray scatters, if it does before t_max.
+    fn scatter_distance(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let crossings = self
+            .boundary
+            .crossings(r, -Float::INFINITY, Float::INFINITY);
 
-        let h2 = self.boundary.hit(r, rec1.t + 0.0001, f32::INFINITY);
-        if h2.is_none() {
-            return None;
+        // The spans of the ray inside the boundary.  Walking all of them lets
+        // the boundary be any closed shape, not only a convex one.
+        let mut spans = Vec::new();
+        let mut enter = match crossings.first() {
+            Some(rec) if !rec.front_face => Some(-Float::INFINITY),
+            _ => None,
+        };
+
+        for rec in &crossings {
+            match (enter, rec.front_face) {
+                (None, true) => enter = Some(rec.t),
+                (Some(t0), false) => {
+                    spans.push((t0, rec.t));
+                    enter = None;
+                }
+                _ => (),
+            }
         }
-        let rec2 = h2.unwrap();
 
-        let mut rec1_t = rec1.t;
-        let mut rec2_t = rec2.t;
+        let ray_length = r.direction.length();
+        let mut hit_distance = self.neg_inv_density * super::random().ln();
 
-        if rec1_t < t_min {
-            rec1_t = t_min;
-        }
-        if rec2_t > t_max {
-            rec2_t = t_max;
-        }
+        for (t0, t1) in spans {
+            let t0 = t0.max(t_min).max(0.0);
+            let t1 = t1.min(t_max);
 
-        if rec1_t >= rec2_t {
-            return None;
-        }
+            if t0 >= t1 {
+                continue;
+            }
 
-        if rec1_t < 0.0 {
-            rec1_t = 0.0;
-        }
+            let distance_inside_boundary = (t1 - t0) * ray_length;
 
-        let ray_length = r.direction.length();
-        let distance_inside_boundary = (rec2_t - rec1_t) * ray_length;
-        let hit_distance = self.neg_inv_density * super::random().ln();
+            // The exponential distribution is memoryless, so the remaining
+            // distance carries over to the next span.
+            if hit_distance > distance_inside_boundary {
+                hit_distance -= distance_inside_boundary;
+                continue;
+            }
 
-        if hit_distance > distance_inside_boundary {
-            return None;
+            return Some(t0 + hit_distance / ray_length);
         }
 
-        let t = rec1_t + hit_distance / ray_length;
+        None
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let t = self.scatter_distance(r, t_min, t_max)?;
 
         Some(HitRecord {
             t,
             p: r.at(t),
+            // There is no surface to leave, so no need to offset.
+            p_error: vec3(0.0, 0.0, 0.0),
             normal: vec3(1.0, 0.0, 0.0),
+            geometric_normal: vec3(1.0, 0.0, 0.0),
             front_face: true,
-            material: self.phase_function.clone(),
+            material: &*self.phase_function,
             u: 0.0,
             v: 0.0,
+            face: 0,
+            mesh: None,
+            object: None,
         })
     }
 
-    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.scatter_distance(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
         self.boundary.bounding_box(time0, time1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::*;
+
+    #[test]
+    fn scatters_inside_a_cube() {
+        let material = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let cube = Rc::new(Cube::new(
+            point3(-1.0, -1.0, -1.0),
+            point3(1.0, 1.0, 1.0),
+            material,
+        ));
+
+        // So dense that every ray scatters right where it enters.
+        let fog = ConstantMedium::with_color(cube.clone(), 1e6, color(1.0, 1.0, 1.0));
+        for n in [
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+        ] {
+            for n in [n, -n] {
+                let r = Ray::new(-5.0 * n, n, 0.0);
+                let rec = fog.hit(&r, 0.0, Float::INFINITY).unwrap();
+                assert!((4.0..4.01).contains(&rec.t), "{} along {:?}", rec.t, n);
+            }
+        }
+
+        // From inside, right away; and never when the ray stops short.
+        let inside = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(fog.hit(&inside, 0.0, Float::INFINITY).unwrap().t < 0.01);
+        let short = Ray::new(point3(0.0, 0.0, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(fog.hit(&short, 0.0, 3.9).is_none());
+
+        // So thin that nothing scatters.
+        let haze = ConstantMedium::with_color(cube, 1e-9, color(1.0, 1.0, 1.0));
+        assert!(haze.hit(&short, 0.0, Float::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+// Constructive solid geometry: a boolean combination of two closed
+// hittables.  The surfaces of `b` that carve into `a` in a difference keep
+// b's material.
+pub struct Csg {
+    op: CsgOp,
+    a: HittableInstance,
+    b: HittableInstance,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: HittableInstance, b: HittableInstance) -> Self {
+        Self { op, a, b }
+    }
+
+    pub fn union(a: HittableInstance, b: HittableInstance) -> Self {
+        Self::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: HittableInstance, b: HittableInstance) -> Self {
+        Self::new(CsgOp::Intersection, a, b)
+    }
+
+    // `a` with `b` carved out of it.
+    pub fn difference(a: HittableInstance, b: HittableInstance) -> Self {
+        Self::new(CsgOp::Difference, a, b)
+    }
+
+    #[inline]
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self.op {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.crossings(r, t_min, t_max).into_iter().next()
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        // Every surface of the result is part of a surface of a or b, so
+        // most rays can be let through without following them both.
+        if !self.a.occluded(r, t_min, t_max) && !self.b.occluded(r, t_min, t_max) {
+            return false;
+        }
+
+        !self.crossings(r, t_min, t_max).is_empty()
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
+        let a = self.a.bounding_box(time0, time1);
+        let b = self.b.bounding_box(time0, time1);
+
+        match self.op {
+            CsgOp::Union => Some(AABB::surrounding_box(&a?, &b?)),
+            CsgOp::Intersection => match (a, b) {
+                (Some(a), Some(b)) => {
+                    let min = vec3(
+                        a.min.x.max(b.min.x),
+                        a.min.y.max(b.min.y),
+                        a.min.z.max(b.min.z),
+                    );
+                    let max = vec3(
+                        a.max.x.min(b.max.x),
+                        a.max.y.min(b.max.y),
+                        a.max.z.min(b.max.z),
+                    );
+                    // Disjoint boxes leave an empty, inside out box that no
+                    // ray hits.
+                    Some(aabb(min, max))
+                }
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => a,
+        }
+    }
+
+    fn crossings(&self, r: &Ray, t_min: Float, t_max: Float) -> Vec<HitRecord<'_>> {
+        // Follow the whole line from -infinity, where it is outside both
+        // shapes, so that we know whether it is inside them at t_min.
+        let mut a = self
+            .a
+            .crossings(r, -Float::INFINITY, t_max)
+            .into_iter()
+            .peekable();
+        let mut b = self
+            .b
+            .crossings(r, -Float::INFINITY, t_max)
+            .into_iter()
+            .peekable();
+
+        let mut in_a = false;
+        let mut in_b = false;
+        let mut inside = false;
+        let mut crossings = Vec::new();
+
+        loop {
+            let from_a = match (a.peek(), b.peek()) {
+                (Some(ra), Some(rb)) => ra.t <= rb.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            let mut rec = if from_a {
+                let rec = a.next().unwrap();
+                in_a = rec.front_face;
+                rec
+            } else {
+                let rec = b.next().unwrap();
+                in_b = rec.front_face;
+                rec
+            };
+
+            if self.inside(in_a, in_b) == inside {
+                continue;
+            }
+            inside = !inside;
+
+            if rec.t < t_min {
+                continue;
+            }
+
+            // The normal always faces against the ray, so only whether this
+            // enters or leaves the combined shape changes.
+            rec.front_face = inside;
+            crossings.push(rec);
+        }
+
+        crossings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::*;
+    use crate::material::*;
+    use crate::sphere::*;
+
+    use std::rc::Rc;
+
+    fn sphere(x: Float, radius: Float) -> HittableInstance {
+        let material = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        Rc::new(Sphere::new(point3(x, 0.0, 0.0), radius, material))
+    }
+
+    fn ts(shape: &Csg, r: &Ray) -> Vec<(Float, bool)> {
+        shape
+            .crossings(r, 0.0, Float::INFINITY)
+            .iter()
+            .map(|rec| (rec.t, rec.front_face))
+            .collect()
+    }
+
+    #[test]
+    fn combines_intervals() {
+        let r = Ray::new(point3(-10.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+
+        // Two overlapping spheres covering x in [-2, 0] and [-1, 1].
+        let union = Csg::union(sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_eq!(ts(&union, &r), vec![(8.0, true), (11.0, false)]);
+
+        let intersection = Csg::intersection(sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_eq!(ts(&intersection, &r), vec![(9.0, true), (10.0, false)]);
+
+        let difference = Csg::difference(sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_eq!(ts(&difference, &r), vec![(8.0, true), (9.0, false)]);
+    }
+
+    #[test]
+    fn carved_surface_faces_into_the_hole() {
+        // A shell: the inner sphere is carved out of the outer one.
+        let shell = Csg::difference(sphere(0.0, 2.0), sphere(0.0, 1.0));
+
+        // From the center, the ray first enters the shell at the inner
+        // sphere, whose normal must point back towards the center.
+        let r = Ray::new(point3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let rec = shell.hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert_eq!(rec.t, 1.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, vec3(-1.0, 0.0, 0.0));
+
+        // Starting inside the shell, the first crossing leaves it.
+        let r = Ray::new(point3(1.5, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let rec = shell.hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert_eq!(rec.t, 0.5);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn carves_a_sphere_out_of_a_cube() {
+        let material = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let cube: HittableInstance = Rc::new(Cube::new(
+            point3(-1.0, -1.0, -1.0),
+            point3(1.0, 1.0, 1.0),
+            material,
+        ));
+        // From `z` on the Z axis, towards the cube.
+        let along_z = |z: Float| Ray::new(point3(0.0, 0.0, z), vec3(0.0, 0.0, -z.signum()), 0.0);
+
+        // A sphere nowhere near the cube leaves it whole, from either side.
+        let whole = Csg::difference(cube.clone(), sphere(10.0, 1.0));
+        assert_eq!(ts(&whole, &along_z(-5.0)), vec![(4.0, true), (6.0, false)]);
+        assert_eq!(ts(&whole, &along_z(5.0)), vec![(4.0, true), (6.0, false)]);
+
+        // One over the +X face takes a bite out of it.
+        let bitten = Csg::difference(cube, sphere(1.0, 0.5));
+        let r = Ray::new(point3(5.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        assert_eq!(ts(&bitten, &r), vec![(4.5, true), (6.0, false)]);
+        assert_eq!(
+            bitten.hit(&r, 0.0, Float::INFINITY).unwrap().normal,
+            vec3(1.0, 0.0, 0.0)
+        );
+    }
+}
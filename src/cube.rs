@@ -2,87 +2,131 @@ use crate::aabb::*;
 use crate::hittable::*;
 use crate::hittable_list::*;
 use crate::material::*;
+use crate::quad::*;
 use crate::ray::*;
-use crate::rect;
 use crate::vec3::*;
 
 use std::rc::Rc;
 
 pub struct Cube {
-    min: Point3,
-    max: Point3,
+    bbox: AABB,
     sides: HittableList,
 }
 
 impl Cube {
     pub fn new(p0: Point3, p1: Point3, material: Rc<dyn Material>) -> Self {
+        // Build it like any other box, so the faces point outwards and
+        // front_face tells entering from leaving.
+        let size = p1 - p0;
+
+        Self::oriented(
+            p0,
+            vec3(size.x, 0.0, 0.0),
+            vec3(0.0, size.y, 0.0),
+            vec3(0.0, 0.0, size.z),
+            material,
+        )
+    }
+
+    // A box with one corner at `origin` and edges `x`, `y` and `z`, which
+    // need not be axis aligned or even perpendicular.
+    pub fn oriented(origin: Point3, x: Vec3, y: Vec3, z: Vec3, material: Rc<dyn Material>) -> Self {
+        // Keep the edges right handed so that every face points outwards.
+        let (x, y) = if x.cross(&y).dot(&z) < 0.0 {
+            (y, x)
+        } else {
+            (x, y)
+        };
+
         let mut sides = HittableList::new();
 
-        sides.add(Rc::new(rect::XY::new(
-            p0.x,
-            p1.x,
-            p0.y,
-            p1.y,
-            p1.z,
-            material.clone(),
-        )));
-        sides.add(Rc::new(rect::XY::new(
-            p0.x,
-            p1.x,
-            p0.y,
-            p1.y,
-            p0.z,
-            material.clone(),
-        )));
-
-        sides.add(Rc::new(rect::XZ::new(
-            p0.x,
-            p1.x,
-            p0.z,
-            p1.z,
-            p1.y,
-            material.clone(),
-        )));
-        sides.add(Rc::new(rect::XZ::new(
-            p0.x,
-            p1.x,
-            p0.z,
-            p1.z,
-            p0.y,
-            material.clone(),
-        )));
-
-        sides.add(Rc::new(rect::YZ::new(
-            p0.y,
-            p1.y,
-            p0.z,
-            p1.z,
-            p1.x,
-            material.clone(),
-        )));
-        sides.add(Rc::new(rect::YZ::new(
-            p0.y,
-            p1.y,
-            p0.z,
-            p1.z,
-            p0.x,
-            material.clone(),
-        )));
-
-        Self {
-            sides,
-            min: p0,
-            max: p1,
-        }
+        sides.add(Rc::new(Quad::new(origin + z, x, y, material.clone()))); // front
+        sides.add(Rc::new(Quad::new(origin + x + z, -z, y, material.clone()))); // right
+        sides.add(Rc::new(Quad::new(origin + x, -x, y, material.clone()))); // back
+        sides.add(Rc::new(Quad::new(origin, z, y, material.clone()))); // left
+        sides.add(Rc::new(Quad::new(origin + y + z, x, -z, material.clone()))); // top
+        sides.add(Rc::new(Quad::new(origin, x, z, material))); // bottom
+
+        let bbox = sides
+            .bounding_box(0.0, 1.0)
+            .expect("Quads always have a bounding box");
+
+        Self { sides, bbox }
     }
 }
 
 impl Hittable for Cube {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
         self.sides.hit(r, t_min, t_max)
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
-        Some(aabb(self.min, self.max))
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.sides.occluded(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every face of `cube`, around `center`, should face away from it along
+    // the directions in `outward`.
+    fn assert_faces_outwards(cube: &Cube, center: Point3, outward: &[Vec3]) {
+        for &n in outward {
+            let into = Ray::new(center + 5.0 * n, -n, 0.0);
+            let rec = cube.hit(&into, 0.0, Float::INFINITY).unwrap();
+            assert!(rec.front_face);
+            assert!((rec.geometric_normal - n).length() < 1e-4);
+
+            let out_of = Ray::new(center, n, 0.0);
+            let rec = cube.hit(&out_of, 0.0, Float::INFINITY).unwrap();
+            assert!(!rec.front_face);
+            assert!((rec.geometric_normal - n).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn axis_aligned_boxes_face_outwards() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let cube = Cube::new(point3(-1.0, -1.0, -1.0), point3(1.0, 2.0, 3.0), material);
+
+        let x = vec3(1.0, 0.0, 0.0);
+        let y = vec3(0.0, 1.0, 0.0);
+        let z = vec3(0.0, 0.0, 1.0);
+        assert_faces_outwards(&cube, point3(0.0, 0.5, 1.0), &[x, -x, y, -y, z, -z]);
+
+        // Through the box: in, then out.
+        let r = Ray::new(point3(0.0, 0.0, -5.0), z, 0.0);
+        let crossings: Vec<(Float, bool)> = cube
+            .crossings(&r, 0.0, Float::INFINITY)
+            .iter()
+            .map(|rec| (rec.t, rec.front_face))
+            .collect();
+        assert_eq!(crossings, vec![(4.0, true), (8.0, false)]);
+    }
+
+    #[test]
+    fn oriented_boxes_face_outwards() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        // Turned 45 degrees about Z, with the edges given either way round.
+        let x = vec3(1.0, 1.0, 0.0);
+        let y = vec3(-1.0, 1.0, 0.0);
+        let z = vec3(0.0, 0.0, 2.0);
+        let center = point3(0.0, 1.0, 1.0);
+        let outward = [x, -x, y, -y, z, -z].map(|n| n.unit());
+
+        for (x, y) in [(x, y), (y, x)] {
+            let cube = Cube::oriented(point3(0.0, 0.0, 0.0), x, y, z, material.clone());
+            assert_faces_outwards(&cube, center, &outward);
+
+            let bbox = cube.bounding_box(0.0, 1.0).unwrap();
+            assert!((bbox.min - point3(-1.0, 0.0, 0.0)).length() < 1e-3);
+            assert!((bbox.max - point3(1.0, 2.0, 2.0)).length() < 1e-3);
+        }
     }
 }
@@ -0,0 +1,268 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use crate::vec3::consts::PI;
+use std::rc::Rc;
+
+// A cylinder standing on `base`, along the +Y axis.  Use a Transform to
+// orient it differently.
+pub struct Cylinder {
+    base: Point3,
+    radius: Float,
+    height: Float,
+    phi_max: Float,
+    capped: bool,
+    material: Rc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3, radius: Float, height: Float, material: Rc<dyn Material>) -> Self {
+        Self {
+            base,
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: true,
+            material,
+        }
+    }
+
+    // Leave the top and bottom open.
+    pub fn uncapped(self) -> Self {
+        Self {
+            capped: false,
+            ..self
+        }
+    }
+
+    // Only keep the part of the surface from 0 to `angle` degrees around the
+    // axis, measured from +X towards +Z.
+    pub fn with_sweep(self, angle: Float) -> Self {
+        Self {
+            phi_max: angle.clamp(0.0, 360.0).to_radians(),
+            ..self
+        }
+    }
+
+    // The t of the nearest hit, the outward normal there and its uvs.
+    fn intersect(
+        &self,
+        r: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(Float, Vec3, Float, Float)> {
+        let o = r.origin - self.base;
+        let d = r.direction;
+        let mut closest: Option<(Float, Vec3, Float, Float)> = None;
+
+        // The side: x^2 + z^2 = radius^2
+        let a = d.x * d.x + d.z * d.z;
+        let half_b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+
+        for t in quadratic_roots(a, half_b, c)
+            .into_iter()
+            .flat_map(|(t0, t1)| [t0, t1])
+        {
+            if t < t_min || t > t_max {
+                continue;
+            }
+
+            let p = o + t * d;
+            let phi = phi_of(p);
+
+            if p.y >= 0.0 && p.y <= self.height && phi <= self.phi_max {
+                let normal = vec3(p.x, 0.0, p.z) / self.radius;
+                closest = Some((t, normal, phi / self.phi_max, p.y / self.height));
+                break;
+            }
+        }
+
+        if self.capped {
+            for (y, normal) in [(0.0, -1.0), (self.height, 1.0)] {
+                let t_far = closest.map_or(t_max, |c| c.0);
+
+                if let Some((t, u, v)) = cap_hit(o, d, y, self.radius, self.phi_max, t_min, t_far) {
+                    closest = Some((t, vec3(0.0, normal, 0.0), u, v));
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, outward_normal, u, v) = self.intersect(r, t_min, t_max)?;
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        let (p, p_error) = if outward_normal.y == 0.0 {
+            // Put the point back on the side, to bound its error.
+            let local = (r.origin - self.base) + t * r.direction;
+            let scale = self.radius / (local.x * local.x + local.z * local.z).sqrt();
+            let local = vec3(scale * local.x, local.y, scale * local.z);
+            let p = self.base + local;
+
+            (p, gamma(3) * local.abs() + gamma(1) * p.abs())
+        } else {
+            (r.at(t), ray_point_error(r, t))
+        };
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(aabb(
+            self.base - vec3(self.radius, 0.0, self.radius),
+            self.base + vec3(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+// The roots of a t^2 + 2 half_b t + c, smallest first.  When a is zero, as
+// for a ray parallel to the slant of a cone, the one root of the linear
+// equation is returned twice.
+pub(crate) fn quadratic_roots(a: Float, half_b: Float, c: Float) -> Option<(Float, Float)> {
+    if a == 0.0 {
+        if half_b == 0.0 {
+            return None;
+        }
+
+        let t = -c / (2.0 * half_b);
+        return Some((t, t));
+    }
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Take the root that adds terms of the same sign first, and get the other
+    // from their product c / a, which stays accurate as a gets small.
+    let q = -(half_b + discriminant.sqrt().copysign(half_b));
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+
+    let (t0, t1) = (q / a, c / q);
+
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+// The angle of `p` around the Y axis, from +X towards +Z, in [0, 2pi).
+#[inline]
+pub(crate) fn phi_of(p: Vec3) -> Float {
+    let phi = p.z.atan2(p.x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+// Intersect the disk of `radius` around the Y axis at height `y`, returning
+// t and the uv of the hit.
+pub(crate) fn cap_hit(
+    o: Vec3,
+    d: Vec3,
+    y: Float,
+    radius: Float,
+    phi_max: Float,
+    t_min: Float,
+    t_max: Float,
+) -> Option<(Float, Float, Float)> {
+    let t = (y - o.y) / d.y;
+    if !(t_min..=t_max).contains(&t) {
+        return None;
+    }
+
+    let p = o + t * d;
+    let dist_squared = p.x * p.x + p.z * p.z;
+    if dist_squared > radius * radius {
+        return None;
+    }
+
+    let phi = phi_of(p);
+    if phi > phi_max {
+        return None;
+    }
+
+    Some((t, phi / phi_max, dist_squared.sqrt() / radius))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cylinder() -> Cylinder {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        Cylinder::new(point3(0.0, 0.0, 0.0), 1.0, 2.0, material)
+    }
+
+    #[test]
+    fn hits_the_side_and_caps() {
+        let cylinder = cylinder();
+
+        let side = Ray::new(point3(5.0, 1.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let rec = cylinder.hit(&side, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert_eq!(rec.normal, vec3(1.0, 0.0, 0.0));
+
+        let top = Ray::new(point3(0.5, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let rec = cylinder.hit(&top, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-5);
+        assert_eq!(rec.normal, vec3(0.0, 1.0, 0.0));
+
+        let over = Ray::new(point3(5.0, 3.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        assert!(cylinder.hit(&over, 0.0, Float::INFINITY).is_none());
+
+        // Straight down the axis of an open one there is nothing to hit.
+        let open = cylinder.uncapped();
+        assert!(open.hit(&top, 0.0, Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn keeps_only_the_swept_part() {
+        let quarter = cylinder().uncapped().with_sweep(90.0);
+
+        // The near side, at -X, is cut away, so the ray hits the inside of
+        // the far side.
+        let r = Ray::new(point3(-5.0, 1.0, 0.1), vec3(1.0, 0.0, 0.0), 0.0);
+        let rec = quarter.hit(&r, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.p.x - 0.995).abs() < 1e-3);
+        assert!(!rec.front_face);
+        assert!(rec.normal.x < 0.0);
+
+        let behind = Ray::new(point3(-5.0, 1.0, -0.1), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(quarter.hit(&behind, 0.0, Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn solves_quadratics() {
+        assert_eq!(quadratic_roots(1.0, -2.5, 4.0), Some((1.0, 4.0)));
+        assert_eq!(quadratic_roots(1.0, 0.0, 1.0), None);
+        assert_eq!(quadratic_roots(0.0, 1.0, -4.0), Some((2.0, 2.0)));
+        assert_eq!(quadratic_roots(0.0, 0.0, 1.0), None);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use crate::vec3::consts::PI;
+use std::rc::Rc;
+
+const PADDING: Float = 0.0001;
+
+// A flat disk facing `normal`, or an annulus when the inner radius is
+// non-zero.
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    // Orthonormal basis of the plane of the disk, used for the uvs.
+    tangent: Vec3,
+    bitangent: Vec3,
+    inner_radius: Float,
+    radius: Float,
+    material: Rc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: Float, material: Rc<dyn Material>) -> Self {
+        Self::annulus(center, normal, 0.0, radius, material)
+    }
+
+    pub fn annulus(
+        center: Point3,
+        normal: Vec3,
+        inner_radius: Float,
+        radius: Float,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        let normal = normal.unit();
+        let (tangent, bitangent) = basis(normal);
+
+        Self {
+            center,
+            normal,
+            tangent,
+            bitangent,
+            inner_radius,
+            radius,
+            material,
+        }
+    }
+
+    // The t of the hit, the point hit, its offset from the center and the
+    // square of its distance from it.
+    fn intersect(
+        &self,
+        r: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(Float, Point3, Vec3, Float)> {
+        let denom = self.normal.dot(&r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.center - r.origin).dot(&self.normal) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let offset = p - self.center;
+        let dist_squared = offset.length_squared();
+
+        if dist_squared > self.radius * self.radius
+            || dist_squared < self.inner_radius * self.inner_radius
+        {
+            return None;
+        }
+
+        Some((t, p, offset, dist_squared))
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, p, offset, dist_squared) = self.intersect(r, t_min, t_max)?;
+
+        let phi = offset.dot(&self.bitangent).atan2(offset.dot(&self.tangent)) + PI;
+        let u = phi / (2.0 * PI);
+        let v = (dist_squared.sqrt() - self.inner_radius) / (self.radius - self.inner_radius);
+
+        let (normal, front_face) = face_normal_and_is_front(r, self.normal);
+
+        Some(HitRecord {
+            p,
+            p_error: ray_point_error(r, t),
+            normal,
+            geometric_normal: self.normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        // The extent of a circle along each axis is radius * sin(angle between
+        // the normal and the axis).
+        let n = self.normal;
+        let extent = self.radius
+            * vec3(
+                (1.0 - n.x * n.x).max(0.0).sqrt(),
+                (1.0 - n.y * n.y).max(0.0).sqrt(),
+                (1.0 - n.z * n.z).max(0.0).sqrt(),
+            )
+            + vec3(PADDING, PADDING, PADDING);
+
+        Some(aabb(self.center - extent, self.center + extent))
+    }
+}
+
+// Two unit vectors completing an orthonormal basis with the unit vector `n`.
+pub(crate) fn basis(n: Vec3) -> (Vec3, Vec3) {
+    // Any vector not parallel to n will do.
+    let helper = if n.x.abs() > 0.9 {
+        vec3(0.0, 1.0, 0.0)
+    } else {
+        vec3(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(&n).unit();
+
+    (tangent, n.cross(&tangent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_inside_the_rim_only() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let disk = Disk::new(
+            point3(0.0, 1.0, 0.0),
+            vec3(0.0, 2.0, 0.0),
+            1.0,
+            material.clone(),
+        );
+        let down = |x: Float| Ray::new(point3(x, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+
+        let rec = disk.hit(&down(0.5), 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert_eq!(rec.normal, vec3(0.0, 1.0, 0.0));
+        assert!((rec.v - 0.5).abs() < 1e-5);
+
+        // From below the normal faces the other way.
+        let up = Ray::new(point3(0.5, -5.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        let rec = disk.hit(&up, 0.0, Float::INFINITY).unwrap();
+        assert_eq!(rec.normal, vec3(0.0, -1.0, 0.0));
+        assert!(!rec.front_face);
+
+        assert!(disk.hit(&down(1.5), 0.0, Float::INFINITY).is_none());
+        let along = Ray::new(point3(-5.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(disk.hit(&along, 0.0, Float::INFINITY).is_none());
+
+        let ring = Disk::annulus(
+            point3(0.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            0.4,
+            1.0,
+            material,
+        );
+        assert!(ring.hit(&down(0.2), 0.0, Float::INFINITY).is_none());
+        assert!(ring.hit(&down(0.7), 0.0, Float::INFINITY).is_some());
+    }
+}
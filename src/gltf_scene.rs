@@ -0,0 +1,442 @@
+use crate::scenes::Scene;
+use crate::*;
+
+use gltf::camera::Projection;
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+
+impl Scene {
+    // Load the default scene (or the first one) of a .gltf or .glb file.
+    //
+    // Each mesh primitive becomes a Mesh with the node transforms baked into
+    // its vertices.  The first camera found is used for the view, with its
+    // roll and aspect ratio, otherwise the camera looks at the whole scene
+    // down the -Z axis.
+    pub fn from_gltf(filename: &str) -> io::Result<Self> {
+        let (document, buffers, images) = gltf::import(filename).map_err(invalid)?;
+        Importer::new(&buffers, &images).import(&document)
+    }
+}
+
+struct Importer<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    textures: HashMap<usize, Rc<dyn Texture>>,
+    materials: HashMap<Option<usize>, Rc<dyn Material>>,
+    world: Vec<HittableInstance>,
+    names: HashMap<u32, String>,
+    camera: Option<GltfCamera>,
+}
+
+// Where the camera is and what it looks at, which way is up, and its
+// vertical field of view in degrees and aspect ratio.
+#[derive(Copy, Clone)]
+struct GltfCamera {
+    lookfrom: Point3,
+    lookat: Point3,
+    vup: Vec3,
+    vfov: Float,
+    aspect_ratio: Float,
+}
+
+impl<'a> Importer<'a> {
+    fn new(buffers: &'a [gltf::buffer::Data], images: &'a [gltf::image::Data]) -> Self {
+        Self {
+            buffers,
+            images,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            world: Vec::new(),
+            names: HashMap::new(),
+            camera: None,
+        }
+    }
+
+    fn import(mut self, document: &gltf::Document) -> io::Result<Scene> {
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| invalid("glTF file contains no scenes"))?;
+
+        for node in scene.nodes() {
+            self.visit(&node, &Mat4::IDENTITY)?;
+        }
+
+        let camera = match self.camera {
+            Some(camera) => camera,
+            None => self.framing_camera(),
+        };
+
+        Ok(Scene {
+            world: self.world,
+            names: self.names,
+            background: color(0.7, 0.8, 1.0),
+            lookfrom: camera.lookfrom,
+            lookat: camera.lookat,
+            vup: camera.vup,
+            vfov: camera.vfov,
+            aspect_ratio: camera.aspect_ratio,
+            aperture: 0.0,
+            samples_per_pixel: 100,
+            accelerator: AcceleratorKind::Bvh,
+        })
+    }
+
+    fn visit(&mut self, node: &gltf::Node, parent: &Mat4) -> io::Result<()> {
+        let transform =
+            *parent * Mat4::from_cols(node.transform().matrix().map(|col| col.map(|x| x as Float)));
+
+        if let Some(mesh) = node.mesh() {
+            // Each primitive becomes an object of its own, named after the
+            // node or else the mesh.
+            let name = node.name().or(mesh.name());
+
+            for primitive in mesh.primitives() {
+                let id = self.world.len() as u32;
+                self.add_primitive(&primitive, &transform)?;
+
+                // Primitives that are not triangles are skipped.
+                match name {
+                    Some(name) if self.world.len() as u32 > id => {
+                        self.names.insert(id, name.to_string());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let (Some(camera), None) = (node.camera(), self.camera) {
+            if let Projection::Perspective(perspective) = camera.projection() {
+                // The camera looks down its local -Z, with +Y up, so any
+                // roll of the node carries over.
+                let lookfrom = transform.transform_point(point3(0.0, 0.0, 0.0));
+                let forward = transform.transform_vector(vec3(0.0, 0.0, -1.0));
+                let up = transform.transform_vector(vec3(0.0, 1.0, 0.0));
+
+                self.camera = Some(GltfCamera {
+                    lookfrom,
+                    lookat: lookfrom + forward.unit(),
+                    vup: up.unit(),
+                    vfov: perspective.yfov().to_degrees() as Float,
+                    aspect_ratio: perspective.aspect_ratio().unwrap_or(1.0) as Float,
+                });
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, &transform)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_primitive(&mut self, primitive: &gltf::Primitive, transform: &Mat4) -> io::Result<()> {
+        if primitive.mode() != Mode::Triangles {
+            return Ok(());
+        }
+
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let positions: Vec<Point3> = reader
+            .read_positions()
+            .ok_or_else(|| invalid("mesh primitive has no positions"))?
+            .map(|[x, y, z]| transform.transform_point(point3(x as Float, y as Float, z as Float)))
+            .collect();
+
+        let inverse = transform
+            .inverse()
+            .ok_or_else(|| invalid("node transform is not invertible"))?;
+        let normals: Vec<Vec3> = reader
+            .read_normals()
+            .map(|normals| {
+                normals
+                    .map(|[x, y, z]| {
+                        inverse
+                            .transform_normal(vec3(x as Float, y as Float, z as Float))
+                            .unit()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // glTF puts the origin of the texture at the top left, while
+        // ImageTexture expects it at the bottom left.
+        let uvs: Vec<(Float, Float)> = reader
+            .read_tex_coords(0)
+            .map(|uvs| {
+                uvs.into_f32()
+                    .map(|[u, v]| (u as Float, 1.0 - v as Float))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let flat: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        if let Some(index) = flat.iter().find(|&&i| i >= positions.len()) {
+            return Err(invalid(format!("index {} is out of range", index)));
+        }
+
+        let indices: Vec<[usize; 3]> = flat.chunks_exact(3).map(|i| [i[0], i[1], i[2]]).collect();
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let material = self.material(&primitive.material());
+
+        self.world.push(Rc::new(Mesh::new(
+            MeshData {
+                positions,
+                normals,
+                uvs,
+                indices,
+            },
+            material,
+        )));
+
+        Ok(())
+    }
+
+    // Approximate the metallic-roughness model with the materials we have.
+    fn material(&mut self, material: &gltf::Material) -> Rc<dyn Material> {
+        if let Some(cached) = self.materials.get(&material.index()) {
+            return cached.clone();
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color = color(r as Float, g as Float, b as Float);
+
+        // The base color is the texture times the factor, or the factor
+        // alone without a texture.
+        let texture = pbr
+            .base_color_texture()
+            .and_then(|info| self.texture(info.texture().source().index()));
+        let albedo: Rc<dyn Texture> = match texture {
+            Some(texture) if base_color == color(1.0, 1.0, 1.0) => texture,
+            Some(texture) => Rc::new(ScaledTexture::new(texture, base_color)),
+            None => Rc::new(SolidColor::new(base_color)),
+        };
+
+        let [er, eg, eb] = material.emissive_factor();
+        let emissive = material.emissive_strength().unwrap_or(1.0) as Float
+            * color(er as Float, eg as Float, eb as Float);
+
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |t| t.transmission_factor());
+
+        let result: Rc<dyn Material> = if emissive.length_squared() > 0.0 {
+            Rc::new(DiffuseLight::new(emissive))
+        } else if transmission > 0.5 {
+            Rc::new(Dielectric::new(material.ior().unwrap_or(1.5) as Float))
+        } else if pbr.metallic_factor() > 0.5 {
+            Rc::new(Metal::new_from_texture(
+                albedo,
+                pbr.roughness_factor() as Float,
+            ))
+        } else {
+            Rc::new(Lambertian::new_from_texture(albedo))
+        };
+
+        self.materials.insert(material.index(), result.clone());
+
+        result
+    }
+
+    fn texture(&mut self, index: usize) -> Option<Rc<dyn Texture>> {
+        if let Some(cached) = self.textures.get(&index) {
+            return Some(cached.clone());
+        }
+
+        let image = &self.images[index];
+
+        // Reduce every 8 and 16 bit format to RGB bytes.  Floating point
+        // images are not supported.
+        let (channels, bytes_per_channel) = match image.format {
+            Format::R8 => (1, 1),
+            Format::R8G8 => (2, 1),
+            Format::R8G8B8 => (3, 1),
+            Format::R8G8B8A8 => (4, 1),
+            Format::R16 => (1, 2),
+            Format::R16G16 => (2, 2),
+            Format::R16G16B16 => (3, 2),
+            Format::R16G16B16A16 => (4, 2),
+            _ => return None,
+        };
+
+        let stride = channels * bytes_per_channel;
+        let pixels: Vec<u8> = image
+            .pixels
+            .chunks_exact(stride)
+            .flat_map(|pixel| {
+                // 16 bit channels are little endian, so keep the high byte.
+                let channel = |c: usize| pixel[c * bytes_per_channel + bytes_per_channel - 1];
+
+                match channels {
+                    1 | 2 => [channel(0), channel(0), channel(0)],
+                    _ => [channel(0), channel(1), channel(2)],
+                }
+            })
+            .collect();
+
+        let texture: Rc<dyn Texture> = Rc::new(ImageTexture::from_pixels(
+            pixels,
+            image.width,
+            image.height,
+            3,
+        ));
+
+        self.textures.insert(index, texture.clone());
+
+        Some(texture)
+    }
+
+    fn framing_camera(&self) -> GltfCamera {
+        const VFOV: Float = 40.0;
+
+        let mut list = HittableList::new();
+        for object in &self.world {
+            list.add(object.clone());
+        }
+
+        let (lookfrom, lookat) = match list.bounding_box(0.0, 1.0) {
+            Some(bbox) => {
+                let center = 0.5 * (bbox.min + bbox.max);
+                let radius = 0.5 * (bbox.max - bbox.min).length();
+                let distance = radius / (VFOV.to_radians() / 2.0).sin();
+
+                (center + vec3(0.0, 0.0, distance), center)
+            }
+            None => (point3(0.0, 0.0, 1.0), point3(0.0, 0.0, 0.0)),
+        };
+
+        GltfCamera {
+            lookfrom,
+            lookat,
+            vup: vec3(0.0, 1.0, 0.0),
+            vfov: VFOV,
+            aspect_ratio: 1.0,
+        }
+    }
+}
+
+fn invalid<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single triangle in a node translated by (0, 0, -5), seen by a
+    // camera at the origin.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 1] }],
+        "nodes": [
+            { "name": "Triangle", "mesh": 0, "translation": [0.0, 0.0, -5.0] },
+            { "camera": 0 }
+        ],
+        "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.5, "znear": 0.1 } }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+        "materials": [{ "emissiveFactor": [1.0, 0.5, 0.25] }],
+        "accessors": [{
+            "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+            "min": [-1.0, -1.0, 0.0], "max": [1.0, 1.0, 0.0]
+        }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "buffers": [{
+            "byteLength": 36,
+            "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAAAAAAAAgD8AAAAA"
+        }]
+    }"#;
+
+    #[test]
+    fn imports_meshes_and_camera() {
+        let (document, buffers, images) = gltf::import_slice(TRIANGLE.as_bytes()).unwrap();
+        let scene = Importer::new(&buffers, &images).import(&document).unwrap();
+
+        assert_eq!(scene.world.len(), 1);
+        assert_eq!(scene.object_name(0), Some("Triangle"));
+        assert_eq!(scene.lookfrom, point3(0.0, 0.0, 0.0));
+        assert_eq!(scene.lookat, point3(0.0, 0.0, -1.0));
+        assert!((scene.vfov - Float::to_degrees(0.5)).abs() < 1e-4);
+
+        let r = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = scene.world[0].hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert!((rec.t - 5.0).abs() < 1e-5);
+        assert_eq!(
+            rec.material.emitted(0.0, 0.0, &rec.p),
+            color(1.0, 0.5, 0.25)
+        );
+    }
+
+    // The same triangle in a textured, tinted metal, seen by a camera rolled
+    // 90 degrees about its view direction.
+    const TEXTURED_METAL: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 1] }],
+        "nodes": [
+            { "mesh": 0, "translation": [0.0, 0.0, -5.0] },
+            { "camera": 0, "rotation": [0.0, 0.0, 0.70710678, 0.70710678] }
+        ],
+        "cameras": [{
+            "type": "perspective",
+            "perspective": { "yfov": 0.5, "aspectRatio": 2.0, "znear": 0.1 }
+        }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+        "materials": [{
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [0.5, 0.5, 0.5, 1.0],
+                "baseColorTexture": { "index": 0 },
+                "metallicFactor": 1.0,
+                "roughnessFactor": 0.0
+            }
+        }],
+        "textures": [{ "source": 0 }],
+        "images": [{ "bufferView": 1, "mimeType": "image/png" }],
+        "accessors": [{
+            "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+            "min": [-1.0, -1.0, 0.0], "max": [1.0, 1.0, 0.0]
+        }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 69 }
+        ],
+        "buffers": [{
+            "byteLength": 105,
+            "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAAAAAAAAgD8AAAAAiVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAIAAACQd1PeAAAADElEQVR4nGNo+O8AAAPCAcC9GylpAAAAAElFTkSuQmCC"
+        }]
+    }"#;
+
+    #[test]
+    fn imports_textured_metal_and_camera_roll() {
+        let (document, buffers, images) = gltf::import_slice(TEXTURED_METAL.as_bytes()).unwrap();
+        let scene = Importer::new(&buffers, &images).import(&document).unwrap();
+
+        assert!((scene.vup - vec3(-1.0, 0.0, 0.0)).length() < 1e-5);
+        assert_eq!(scene.aspect_ratio, 2.0);
+
+        // The metal takes its color from the texture, tinted by the factor.
+        let r = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = scene.world[0].hit(&r, 0.001, Float::INFINITY).unwrap();
+        let (attenuation, scattered) = rec.material.scatter(&r, &rec).unwrap();
+
+        let expected = 0.5 * color(128.0, 255.0, 64.0) / 255.0;
+        assert!((attenuation - expected).length() < 0.01);
+        assert!(scattered.direction.z > 0.0);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::aabb::*;
+use crate::accelerator::*;
+use crate::hittable::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+// About how many cells to make per object.
+const CELLS_PER_OBJECT: Float = 3.0;
+const MAX_RESOLUTION: usize = 128;
+
+// A uniform grid of cells over the scene, each listing the objects that
+// overlap it.  Rays step through the cells in order with a 3D DDA.  Grids
+// suit scenes of evenly spread, similarly sized objects.
+pub struct UniformGrid {
+    bounds: AABB,
+    resolution: [usize; 3],
+    cell_size: Vec3,
+    // The objects of cell c are cell_objects[cell_start[c]..cell_start[c + 1]].
+    cell_start: Vec<u32>,
+    cell_objects: Vec<u32>,
+    objects: Vec<HittableInstance>,
+    // Objects without a bounding box are tested on every ray.
+    unbounded: Vec<HittableInstance>,
+    build_time: Duration,
+    counters: TraversalCounters,
+}
+
+impl UniformGrid {
+    pub fn new(objects: &[HittableInstance], time0: Float, time1: Float) -> Self {
+        let start = Instant::now();
+        let (bounded, unbounded) = split_bounded(objects, time0, time1);
+
+        let inf = Float::INFINITY;
+        let mut bounds = bounded
+            .iter()
+            .map(|(_, bbox)| *bbox)
+            .reduce(|a, b| AABB::surrounding_box(&a, &b))
+            .unwrap_or_else(|| aabb(point3(inf, inf, inf), point3(-inf, -inf, -inf)));
+
+        // Pick cubic cells, with about CELLS_PER_OBJECT of them per object.
+        let mut resolution = [1; 3];
+        if !bounded.is_empty() {
+            for a in 0..3 {
+                if bounds.max[a] - bounds.min[a] <= 0.0 {
+                    bounds.min[a] -= 0.0001;
+                    bounds.max[a] += 0.0001;
+                }
+            }
+
+            let extent = bounds.max - bounds.min;
+            let volume = extent.x * extent.y * extent.z;
+            let cells_per_unit = (CELLS_PER_OBJECT * bounded.len() as Float / volume).cbrt();
+
+            for a in 0..3 {
+                resolution[a] =
+                    ((extent[a] * cells_per_unit).round() as usize).clamp(1, MAX_RESOLUTION);
+            }
+        }
+
+        let extent = bounds.max - bounds.min;
+        let cell_size = vec3(
+            extent.x / resolution[0] as Float,
+            extent.y / resolution[1] as Float,
+            extent.z / resolution[2] as Float,
+        );
+
+        let mut grid = Self {
+            bounds,
+            resolution,
+            cell_size,
+            cell_start: Vec::new(),
+            cell_objects: Vec::new(),
+            objects: Vec::with_capacity(bounded.len()),
+            unbounded,
+            build_time: Duration::ZERO,
+            counters: TraversalCounters::default(),
+        };
+
+        // Count the objects in each cell first, so they can be stored
+        // contiguously.
+        let cell_count = resolution.iter().product::<usize>();
+        let mut counts = vec![0u32; cell_count + 1];
+        let ranges: Vec<[(usize, usize); 3]> = if bounded.is_empty() {
+            Vec::new()
+        } else {
+            bounded
+                .iter()
+                .map(|(_, bbox)| grid.cell_range(bbox))
+                .collect()
+        };
+
+        for range in &ranges {
+            grid.for_each_cell(range, |cell| counts[cell] += 1);
+        }
+
+        let mut total = 0;
+        for count in counts.iter_mut() {
+            let n = *count;
+            *count = total;
+            total += n;
+        }
+
+        let mut fill = counts.clone();
+        let mut cell_objects = vec![0u32; total as usize];
+        for (index, range) in ranges.iter().enumerate() {
+            grid.for_each_cell(range, |cell| {
+                cell_objects[fill[cell] as usize] = index as u32;
+                fill[cell] += 1;
+            });
+        }
+
+        grid.cell_start = counts;
+        grid.cell_objects = cell_objects;
+        grid.objects = bounded.into_iter().map(|(object, _)| object).collect();
+        grid.build_time = start.elapsed();
+
+        grid
+    }
+
+    // The first and last cell overlapped by a box along each axis.
+    fn cell_range(&self, bbox: &AABB) -> [(usize, usize); 3] {
+        let mut range = [(0, 0); 3];
+
+        for (a, range) in range.iter_mut().enumerate() {
+            let cell = |x: Float| {
+                let c = ((x - self.bounds.min[a]) / self.cell_size[a]).floor();
+                (c.max(0.0) as usize).min(self.resolution[a] - 1)
+            };
+            *range = (cell(bbox.min[a]), cell(bbox.max[a]));
+        }
+
+        range
+    }
+
+    fn for_each_cell<F: FnMut(usize)>(&self, range: &[(usize, usize); 3], mut f: F) {
+        for z in range[2].0..=range[2].1 {
+            for y in range[1].0..=range[1].1 {
+                for x in range[0].0..=range[0].1 {
+                    f(self.cell_index([x, y, z]));
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn cell_index(&self, cell: [usize; 3]) -> usize {
+        (cell[2] * self.resolution[1] + cell[1]) * self.resolution[0] + cell[0]
+    }
+
+    // Pass the objects of each cell the ray goes through within [t_min,
+    // t_max] to `visit`, nearest first.  It returns the t of the closest hit
+    // so far, and the walk stops at the first cell beyond it.
+    fn walk<F: FnMut(&[u32]) -> Float>(&self, r: &Ray, t_min: Float, t_max: Float, mut visit: F) {
+        if self.objects.is_empty() {
+            return;
+        }
+
+        let (t_enter, t_exit) = match self.bounds.clip(r, t_min, t_max) {
+            Some(span) => span,
+            None => return,
+        };
+
+        // Set up the DDA: the cell the ray starts in, and for each axis the
+        // t of the next cell boundary and the t between boundaries.
+        let start = r.at(t_enter) - self.bounds.min;
+        let mut cell = [0usize; 3];
+        let mut next = [Float::INFINITY; 3];
+        let mut delta = [Float::INFINITY; 3];
+
+        for a in 0..3 {
+            let c = (start[a] / self.cell_size[a]).floor();
+            cell[a] = (c.max(0.0) as usize).min(self.resolution[a] - 1);
+
+            let d = r.direction[a];
+            if d > 0.0 {
+                next[a] = t_enter + ((cell[a] + 1) as Float * self.cell_size[a] - start[a]) / d;
+                delta[a] = self.cell_size[a] / d;
+            } else if d < 0.0 {
+                next[a] = t_enter + (cell[a] as Float * self.cell_size[a] - start[a]) / d;
+                delta[a] = -self.cell_size[a] / d;
+            }
+        }
+
+        loop {
+            self.counters.node();
+
+            let index = self.cell_index(cell);
+            let objects = &self.cell_objects
+                [self.cell_start[index] as usize..self.cell_start[index + 1] as usize];
+            self.counters.primitives(objects.len());
+
+            let closest_t = visit(objects);
+
+            let a = if next[0] < next[1] {
+                if next[0] < next[2] {
+                    0
+                } else {
+                    2
+                }
+            } else if next[1] < next[2] {
+                1
+            } else {
+                2
+            };
+
+            // A hit inside this cell is nearer than anything in later cells.
+            if closest_t <= next[a] || next[a] > t_exit {
+                break;
+            }
+
+            if r.direction[a] > 0.0 {
+                cell[a] += 1;
+                if cell[a] == self.resolution[a] {
+                    break;
+                }
+            } else {
+                if cell[a] == 0 {
+                    break;
+                }
+                cell[a] -= 1;
+            }
+            next[a] += delta[a];
+        }
+    }
+}
+
+impl Hittable for UniformGrid {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.counters.ray();
+        self.counters.primitives(self.unbounded.len());
+
+        let mut rec: Option<HitRecord> = None;
+        let mut closest_t = t_max;
+
+        for object in &self.unbounded {
+            if let Some(r) = object.hit(r, t_min, closest_t) {
+                closest_t = r.t;
+                rec = Some(r);
+            }
+        }
+
+        self.walk(r, t_min, closest_t, |objects| {
+            for &object in objects {
+                if let Some(r) = self.objects[object as usize].hit(r, t_min, closest_t) {
+                    closest_t = r.t;
+                    rec = Some(r);
+                }
+            }
+
+            closest_t
+        });
+
+        rec
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.counters.ray();
+        self.counters.primitives(self.unbounded.len());
+
+        if self
+            .unbounded
+            .iter()
+            .any(|object| object.occluded(r, t_min, t_max))
+        {
+            return true;
+        }
+
+        let mut occluded = false;
+        self.walk(r, t_min, t_max, |objects| {
+            occluded = objects
+                .iter()
+                .any(|&object| self.objects[object as usize].occluded(r, t_min, t_max));
+
+            // Stop at the first hit.
+            if occluded {
+                -Float::INFINITY
+            } else {
+                t_max
+            }
+        });
+
+        occluded
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        Some(self.bounds)
+    }
+}
+
+impl Accelerator for UniformGrid {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn stats(&self) -> AcceleratorStats {
+        let memory = (self.cell_start.len() + self.cell_objects.len()) * size_of::<u32>()
+            + (self.objects.len() + self.unbounded.len()) * size_of::<HittableInstance>();
+
+        self.counters
+            .stats(self.build_time, memory, self.cell_start.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accelerator::tests::*;
+
+    #[test]
+    fn matches_a_plain_list() {
+        let objects = clumpy_objects();
+        let accelerator = UniformGrid::new(&objects, 0.0, 1.0);
+
+        assert_matches_a_plain_list(&accelerator, &objects);
+    }
+}
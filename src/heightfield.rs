@@ -0,0 +1,397 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::perlin::*;
+use crate::ray::*;
+use crate::triangle::{barycentric_point, intersect};
+use crate::vec3::*;
+
+use jpeg_decoder::Decoder;
+use std::fs::File;
+use std::io::BufReader;
+use std::rc::Rc;
+
+const PADDING: Float = 0.0001;
+
+// Terrain from a grid of `nx` by `nz` elevations, stored row by row along
+// +X, rows along +Z.  The grid spans `width` by `depth` from `corner`, and
+// each cell is split into two triangles.  The uvs run from 0 to 1 across the
+// grid.
+pub struct HeightField {
+    corner: Point3,
+    width: Float,
+    depth: Float,
+    nx: usize,
+    nz: usize,
+    heights: Vec<Float>,
+    normals: Vec<Vec3>,
+    // The lowest and highest elevation of each cell, to skip cells the ray
+    // passes above or below.
+    cell_bounds: Vec<(Float, Float)>,
+    bbox: AABB,
+    material: Rc<dyn Material>,
+}
+
+impl HeightField {
+    pub fn new(
+        corner: Point3,
+        width: Float,
+        depth: Float,
+        nx: usize,
+        nz: usize,
+        heights: Vec<Float>,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        assert!(
+            nx >= 2 && nz >= 2,
+            "A height field needs at least 2x2 samples"
+        );
+        assert_eq!(
+            heights.len(),
+            nx * nz,
+            "Height data does not match the grid dimensions"
+        );
+
+        let dx = width / (nx - 1) as Float;
+        let dz = depth / (nz - 1) as Float;
+        let h = |i: usize, j: usize| heights[j * nx + i];
+
+        // Central differences inside the grid, one sided along the edges.
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+
+                let slope_x = (h(i1, j) - h(i0, j)) / ((i1 - i0) as Float * dx);
+                let slope_z = (h(i, j1) - h(i, j0)) / ((j1 - j0) as Float * dz);
+
+                normals.push(vec3(-slope_x, 1.0, -slope_z).unit());
+            }
+        }
+
+        let mut cell_bounds = Vec::with_capacity((nx - 1) * (nz - 1));
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let corners = [h(i, j), h(i + 1, j), h(i, j + 1), h(i + 1, j + 1)];
+                let low = corners.iter().copied().fold(Float::INFINITY, Float::min);
+                let high = corners.iter().copied().fold(-Float::INFINITY, Float::max);
+
+                cell_bounds.push((low, high));
+            }
+        }
+
+        let low = heights.iter().copied().fold(Float::INFINITY, Float::min);
+        let high = heights.iter().copied().fold(-Float::INFINITY, Float::max);
+        let bbox = aabb(
+            corner + vec3(0.0, low - PADDING, 0.0),
+            corner + vec3(width, high + PADDING, depth),
+        );
+
+        Self {
+            corner,
+            width,
+            depth,
+            nx,
+            nz,
+            heights,
+            normals,
+            cell_bounds,
+            bbox,
+            material,
+        }
+    }
+
+    // Use the brightness of a grayscale (or color) JPEG as the elevation,
+    // scaled so that white is `size.y` high.  The top of the image is the far
+    // (+Z) edge, like the uvs of ImageTexture.
+    pub fn from_image(
+        filename: &str,
+        corner: Point3,
+        size: Vec3,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        let file = File::open(filename).expect("failed to open file");
+        let mut decoder = Decoder::new(BufReader::new(file));
+        let pixels = decoder.decode().expect("failed to decode image");
+        let metadata = decoder.info().unwrap();
+
+        let bytes_per_pixel = metadata.pixel_format.pixel_bytes();
+        let nx = metadata.width as usize;
+        let nz = metadata.height as usize;
+
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            let row = (nz - 1 - j) * nx;
+
+            for i in 0..nx {
+                let p = &pixels[(row + i) * bytes_per_pixel..];
+                let brightness = match bytes_per_pixel {
+                    1 => p[0] as Float / 255.0,
+                    2 => u16::from_be_bytes([p[0], p[1]]) as Float / 65535.0,
+                    _ => {
+                        (0.2126 * p[0] as Float + 0.7152 * p[1] as Float + 0.0722 * p[2] as Float)
+                            / 255.0
+                    }
+                };
+
+                heights.push(size.y * brightness);
+            }
+        }
+
+        Self::new(corner, size.x, size.z, nx, nz, heights, material)
+    }
+
+    // Random rolling hills from Perlin turbulence, `scale` features per unit
+    // across the ground, rising up to `size.y`.
+    pub fn from_perlin(
+        corner: Point3,
+        size: Vec3,
+        nx: usize,
+        nz: usize,
+        scale: Float,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        let noise = Perlin::new();
+
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let x = size.x * i as Float / (nx - 1) as Float;
+                let z = size.z * j as Float / (nz - 1) as Float;
+
+                heights.push(noise.turb(&(scale * point3(x, 0.0, z)), 7));
+            }
+        }
+
+        let highest = heights.iter().copied().fold(0.0, Float::max);
+        if highest > 0.0 {
+            for h in heights.iter_mut() {
+                *h *= size.y / highest;
+            }
+        }
+
+        Self::new(corner, size.x, size.z, nx, nz, heights, material)
+    }
+
+    #[inline]
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        self.corner
+            + vec3(
+                self.width * i as Float / (self.nx - 1) as Float,
+                self.heights[j * self.nx + i],
+                self.depth * j as Float / (self.nz - 1) as Float,
+            )
+    }
+
+    // Intersect the two triangles of cell (i, j).
+    fn cell_hit(
+        &self,
+        r: &Ray,
+        i: usize,
+        j: usize,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<HitRecord<'_>> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest: Option<(Float, Vec3, Point3, Vec3, Vec3)> = None;
+        let mut t_max = t_max;
+
+        for tri in [[0, 1, 2], [0, 2, 3]] {
+            let [a, b, c] = tri.map(|k| corners[k]);
+
+            let (v0, v1, v2) = (
+                self.vertex(a.0, a.1),
+                self.vertex(b.0, b.1),
+                self.vertex(c.0, c.1),
+            );
+
+            if let Some((t, b1, b2)) = intersect(r, v0, v1, v2, t_min, t_max) {
+                let n = |(i, j): (usize, usize)| self.normals[j * self.nx + i];
+                let normal = (1.0 - b1 - b2) * n(a) + b1 * n(b) + b2 * n(c);
+                let (p, p_error) = barycentric_point(v0, v1, v2, b1, b2);
+                let geometric_normal = (v1 - v0).cross(&(v2 - v0)).unit();
+
+                closest = Some((t, normal.unit(), p, p_error, geometric_normal));
+                t_max = t;
+            }
+        }
+
+        let (t, outward_normal, p, p_error, geometric_normal) = closest?;
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal,
+            material: &*self.material,
+            t,
+            u: ((p.x - self.corner.x) / self.width).clamp(0.0, 1.0),
+            v: ((p.z - self.corner.z) / self.depth).clamp(0.0, 1.0),
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    // Whether either triangle of cell (i, j) is in the way.
+    fn cell_occluded(&self, r: &Ray, i: usize, j: usize, t_min: Float, t_max: Float) -> bool {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+
+        [[0, 1, 2], [0, 2, 3]].iter().any(|tri| {
+            let [a, b, c] = tri.map(|k| corners[k]);
+            let (v0, v1, v2) = (
+                self.vertex(a.0, a.1),
+                self.vertex(b.0, b.1),
+                self.vertex(c.0, c.1),
+            );
+
+            intersect(r, v0, v1, v2, t_min, t_max).is_some()
+        })
+    }
+
+    // Pass the cells under the ray that it may meet the surface in to
+    // `visit`, nearest first, until it returns something.
+    fn walk<T, F>(&self, r: &Ray, t_min: Float, t_max: Float, mut visit: F) -> Option<T>
+    where
+        F: FnMut(usize, usize) -> Option<T>,
+    {
+        let (t_enter, t_exit) = self.bbox.clip(r, t_min, t_max)?;
+
+        let cells_x = self.nx - 1;
+        let cells_z = self.nz - 1;
+        let dx = self.width / cells_x as Float;
+        let dz = self.depth / cells_z as Float;
+
+        // Walk the cells under the ray with a 2D DDA, nearest first.
+        let start = r.at(t_enter) - self.corner;
+        let cell = |x: Float, size: Float, count: usize| {
+            ((x / size).floor().max(0.0) as usize).min(count - 1)
+        };
+        let mut i = cell(start.x, dx, cells_x);
+        let mut j = cell(start.z, dz, cells_z);
+
+        let d = r.direction;
+        let axis = |i: usize, size: Float, origin: Float, d: Float| {
+            if d > 0.0 {
+                ((i + 1) as Float * size - origin) / d
+            } else if d < 0.0 {
+                (i as Float * size - origin) / d
+            } else {
+                Float::INFINITY
+            }
+        };
+        let o = r.origin - self.corner;
+        let mut t_next_x = axis(i, dx, o.x, d.x);
+        let mut t_next_z = axis(j, dz, o.z, d.z);
+        let t_delta_x = (dx / d.x).abs();
+        let t_delta_z = (dz / d.z).abs();
+
+        let mut t = t_enter;
+
+        loop {
+            let t_cell_exit = t_next_x.min(t_next_z).min(t_exit);
+
+            // Only test the triangles when the ray is within the elevations
+            // of the cell.
+            let (low, high) = self.cell_bounds[j * cells_x + i];
+            let y0 = o.y + t * d.y;
+            let y1 = o.y + t_cell_exit * d.y;
+
+            if y0.min(y1) <= high + PADDING && y0.max(y1) >= low - PADDING {
+                if let Some(found) = visit(i, j) {
+                    return Some(found);
+                }
+            }
+
+            if t_cell_exit >= t_exit {
+                return None;
+            }
+
+            if t_next_x < t_next_z {
+                if d.x > 0.0 {
+                    i += 1;
+                } else {
+                    i = i.checked_sub(1)?;
+                }
+                t = t_next_x;
+                t_next_x += t_delta_x;
+            } else {
+                if d.z > 0.0 {
+                    j += 1;
+                } else {
+                    j = j.checked_sub(1)?;
+                }
+                t = t_next_z;
+                t_next_z += t_delta_z;
+            }
+
+            if i >= cells_x || j >= cells_z {
+                return None;
+            }
+        }
+    }
+}
+
+impl Hittable for HeightField {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        // Triangles never reach outside their cell, so the first hit found is
+        // the nearest.
+        self.walk(r, t_min, t_max, |i, j| self.cell_hit(r, i, j, t_min, t_max))
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.walk(r, t_min, t_max, |i, j| {
+            self.cell_occluded(r, i, j, t_min, t_max).then_some(())
+        })
+        .is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_the_slope_between_samples() {
+        // A ramp rising along +X from 0 to 2.
+        let heights = vec![0.0, 1.0, 2.0, 0.0, 1.0, 2.0, 0.0, 1.0, 2.0];
+        let field = HeightField::new(
+            point3(0.0, 0.0, 0.0),
+            2.0,
+            2.0,
+            3,
+            3,
+            heights,
+            Rc::new(Lambertian::new(color(0.5, 0.5, 0.5))),
+        );
+
+        // Straight down onto x = 1.5, where the ramp is 1.5 high.
+        let r = Ray::new(point3(1.5, 10.0, 0.7), vec3(0.0, -1.0, 0.0), 0.0);
+        let rec = field.hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert!((rec.t - 8.5).abs() < 1e-4);
+        assert!((rec.u - 0.75).abs() < 1e-4);
+        assert!((rec.v - 0.35).abs() < 1e-4);
+        assert!(rec.front_face);
+
+        // Skimming along the ramp from the low end, the ray at y = 1.2
+        // meets it at x = 1.2.
+        let r = Ray::new(point3(-1.0, 1.2, 1.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let rec = field.hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert!((rec.p.x - 1.2).abs() < 1e-4);
+        let slope = vec3(-1.0, 1.0, 0.0).unit();
+        assert!((rec.normal - slope).length() < 1e-4);
+
+        // And passing above it, nothing.
+        let r = Ray::new(point3(-1.0, 2.5, 1.0), vec3(1.0, 0.0, 0.1), 0.0);
+        assert!(field.hit(&r, 0.001, Float::INFINITY).is_none());
+    }
+}
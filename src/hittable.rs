@@ -1,5 +1,8 @@
 use crate::aabb::*;
+use crate::mat4::*;
 use crate::material::*;
+use crate::mesh::*;
+use crate::quat::*;
 use crate::ray::*;
 use crate::vec3::*;
 use std::rc::Rc;
@@ -7,8 +10,38 @@ use std::rc::Rc;
 pub type HittableInstance = Rc<dyn Hittable>;
 
 pub trait Hittable {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
-    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB>;
+    // The nearest hit in [t_min, t_max].  The whole record, normal and uv
+    // included, is built for every hit found, not only for the one that ends
+    // up closest.  Lists and accelerators pass the closest t so far as t_max,
+    // so hits behind it are rejected before any of that work.
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB>;
+
+    // Whether anything is hit in [t_min, t_max], for shadow and visibility
+    // rays.  This can stop at the first hit found and skip building a
+    // HitRecord, so most hittables override it.
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.hit(r, t_min, t_max).is_some()
+    }
+
+    // Every place in [t_min, t_max] where the ray crosses the surface, nearest
+    // first.  For a closed shape, front_face tells whether the ray enters or
+    // leaves it there.  By default this calls hit() repeatedly.
+    fn crossings(&self, r: &Ray, t_min: Float, t_max: Float) -> Vec<HitRecord<'_>> {
+        let mut crossings = Vec::new();
+        let mut t = t_min;
+
+        while let Some(rec) = self.hit(r, t, t_max) {
+            t = rec.next_t(r);
+            crossings.push(rec);
+
+            if !t.is_finite() {
+                break;
+            }
+        }
+
+        crossings
+    }
 }
 
 // TODO: Should return an Enum rather than a tuple with a bool!
@@ -20,144 +53,787 @@ pub fn face_normal_and_is_front(r: &Ray, n: Vec3) -> (Vec3, bool) {
     }
 }
 
-pub struct HitRecord {
+// A bound on the error of r.at(t) when t is where the ray meets a plane,
+// worked out with a handful of operations.  Only the error along the normal
+// matters, and this covers it.
+#[inline]
+pub fn ray_point_error(r: &Ray, t: Float) -> Vec3 {
+    gamma(7) * (r.origin.abs() + (t * r.direction).abs())
+}
+
+pub struct HitRecord<'a> {
     pub p: Point3,
+    // How far p may be from the true surface along each axis, because of
+    // rounding error.
+    pub p_error: Vec3,
     pub normal: Vec3,
-    pub material: Rc<dyn Material>,
-    pub t: f32,
-    pub u: f32,
-    pub v: f32,
+    // The normal of the surface itself, which `normal` may be interpolated
+    // from for smooth shading.  Spawned rays are offset along this one.
+    pub geometric_normal: Vec3,
+    // Borrowed from the hittable, so building a record does not touch a
+    // reference count.
+    pub material: &'a dyn Material,
+    pub t: Float,
+    pub u: Float,
+    pub v: Float,
     pub front_face: bool,
+    // The index of the face that was hit within its mesh, for textures that
+    // look up per-face data.  Zero for anything but a mesh.
+    pub face: u32,
+    // The data of the mesh that was hit, for textures that look up
+    // per-vertex data.  None for anything but a mesh.
+    pub mesh: Option<&'a MeshData>,
+    // The id of the Identified object that was hit, if any.
+    pub object: Option<u32>,
+}
+
+impl HitRecord<'_> {
+    // A ray leaving the surface in `direction`, from far enough off it that
+    // rounding error cannot make the ray hit the same spot again.
+    pub fn spawn_ray(&self, direction: Vec3, time: Float) -> Ray {
+        let origin = offset_ray_origin(self.p, self.p_error, self.geometric_normal, direction);
+        Ray::new(origin, direction, time)
+    }
+
+    // Where to look for the next surface along the same ray `r`, just past
+    // the error around this hit.
+    pub fn next_t(&self, r: &Ray) -> Float {
+        let t = self.t + self.p_error.length() / r.direction.length();
+        t.max(self.t.next_up())
+    }
 }
 
-pub struct Translate {
-    offset: Vec3,
+// Move p along the normal n, to the side that w leaves towards, by as much
+// as it may be off the surface.
+pub fn offset_ray_origin(p: Point3, p_error: Vec3, n: Vec3, w: Vec3) -> Point3 {
+    let d = n.abs().dot(&p_error);
+    let offset = if w.dot(&n) < 0.0 { -d * n } else { d * n };
+    let mut origin = p + offset;
+
+    // Round away from p, so the offset is not lost to rounding itself.
+    for a in 0..3 {
+        if offset[a] > 0.0 {
+            origin[a] = origin[a].next_up();
+        } else if offset[a] < 0.0 {
+            origin[a] = origin[a].next_down();
+        }
+    }
+
+    origin
+}
+
+// Another hittable tagged with an id, which its hits report in
+// HitRecord::object so callers can tell what they hit.  When tagged objects
+// are nested, the outermost id wins.
+pub struct Identified {
+    id: u32,
     instance: HittableInstance,
 }
 
-impl Translate {
-    pub fn new(instance: HittableInstance, offset: Vec3) -> Self {
-        Self { instance, offset }
+impl Identified {
+    pub fn new(id: u32, instance: HittableInstance) -> Self {
+        Self { id, instance }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
     }
 }
 
-impl Hittable for Translate {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let moved_r = Ray::new(r.origin - self.offset, r.direction, r.time);
+impl Hittable for Identified {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let rec = self.instance.hit(r, t_min, t_max)?;
 
-        if let Some(rec) = self.instance.hit(&moved_r, t_min, t_max) {
-            let (normal, front_face) = face_normal_and_is_front(&moved_r, rec.normal);
+        Some(HitRecord {
+            object: Some(self.id),
+            ..rec
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.instance.occluded(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
+        self.instance.bounding_box(time0, time1)
+    }
 
-            Some(HitRecord {
-                p: rec.p + self.offset,
-                normal,
-                front_face,
+    fn crossings(&self, r: &Ray, t_min: Float, t_max: Float) -> Vec<HitRecord<'_>> {
+        self.instance
+            .crossings(r, t_min, t_max)
+            .into_iter()
+            .map(|rec| HitRecord {
+                object: Some(self.id),
                 ..rec
             })
-        } else {
-            None
+            .collect()
+    }
+}
+
+// An instance of another hittable under an affine transform: any mix of
+// translation, rotation, scale and shear.  Many instances can share one
+// prototype, such as a BVHNode over a whole model, each with a transform and
+// optionally a material of its own, for little more memory than the two
+// matrices.
+pub struct Transform {
+    instance: HittableInstance,
+    matrix: Mat4,
+    inverse: Mat4,
+    // Replaces the materials of the prototype when set.
+    material: Option<Rc<dyn Material>>,
+}
+
+impl Transform {
+    pub fn new(instance: HittableInstance, matrix: Mat4) -> Self {
+        let inverse = matrix
+            .inverse()
+            .expect("Transform matrix must be invertible");
+
+        Self {
+            instance,
+            matrix,
+            inverse,
+            material: None,
+        }
+    }
+
+    pub fn with_material(self, material: Rc<dyn Material>) -> Self {
+        Self {
+            material: Some(material),
+            ..self
         }
     }
 
-    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let rec = transformed_hit(&self.instance, &self.matrix, &self.inverse, r, t_min, t_max)?;
+
+        match &self.material {
+            Some(material) => Some(HitRecord {
+                material: material.as_ref(),
+                ..rec
+            }),
+            None => Some(rec),
+        }
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.instance
+            .occluded(&local_ray(&self.inverse, r), t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
         self.instance
             .bounding_box(time0, time1)
-            .map(|output_box| aabb(output_box.min + self.offset, output_box.max + self.offset))
+            .map(|bbox| bbox.transformed(&self.matrix))
     }
 }
 
-pub struct RotateY {
-    sin_theta: f32,
-    cos_theta: f32,
-    instance: HittableInstance,
-    bbox: Option<AABB>,
+// Intersect `instance` in its local space, where `matrix` takes local points
+// to world points and `inverse` is its inverse.
+fn transformed_hit<'a>(
+    instance: &'a HittableInstance,
+    matrix: &Mat4,
+    inverse: &Mat4,
+    r: &Ray,
+    t_min: Float,
+    t_max: Float,
+) -> Option<HitRecord<'a>> {
+    let rec = instance.hit(&local_ray(inverse, r), t_min, t_max)?;
+
+    // The inverse transpose keeps the sign of the normal relative to the
+    // ray, so front_face is unchanged.
+    Some(HitRecord {
+        p: matrix.transform_point(rec.p),
+        p_error: matrix.transform_point_error(rec.p, rec.p_error),
+        normal: inverse.transform_normal(rec.normal).unit(),
+        geometric_normal: inverse.transform_normal(rec.geometric_normal).unit(),
+        ..rec
+    })
+}
+
+// The ray in the local space of a transformed instance.  The direction is not
+// normalized, so t is the same in both spaces.
+#[inline]
+fn local_ray(inverse: &Mat4, r: &Ray) -> Ray {
+    Ray::new(
+        inverse.transform_point(r.origin),
+        inverse.transform_vector(r.direction),
+        r.time,
+    )
 }
 
-impl RotateY {
-    pub fn new(instance: HittableInstance, angle: f32) -> Self {
-        let radians = angle.to_radians();
-        let sin_theta = radians.sin();
-        let cos_theta = radians.cos();
+// A pose of an AnimatedTransform: scale, then rotate, then translate.
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe {
+    pub time: Float,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
 
-        let bbox = if let Some(bbox) = instance.bounding_box(0.0, 1.0) {
-            let mut min = point3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
-            let mut max = point3(-f32::INFINITY, -f32::INFINITY, -f32::INFINITY);
+impl Keyframe {
+    pub fn new(time: Float, translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
 
-            for i in 0..2 {
-                for j in 0..2 {
-                    for k in 0..2 {
-                        let fi = i as f32;
-                        let fj = j as f32;
-                        let fk = k as f32;
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.translation)
+            * Mat4::from_quat(self.rotation)
+            * Mat4::scale(self.scale)
+    }
 
-                        let x = fi * bbox.max.x + (1.0 - fi) * bbox.min.x;
-                        let y = fj * bbox.max.y + (1.0 - fj) * bbox.min.y;
-                        let z = fk * bbox.max.z + (1.0 - fk) * bbox.min.z;
+    fn inverse_matrix(&self) -> Mat4 {
+        let s = self.scale;
 
-                        let newx = cos_theta * x + sin_theta * z;
-                        let newz = -sin_theta * x + cos_theta * z;
+        Mat4::scale(vec3(1.0 / s.x, 1.0 / s.y, 1.0 / s.z))
+            * Mat4::from_quat(self.rotation.conjugate())
+            * Mat4::translation(-self.translation)
+    }
+}
 
-                        let tester = vec3(newx, y, newz);
+// Number of poses sampled between keyframes when bounding the motion.
+const MOTION_STEPS: usize = 16;
 
-                        for c in 0..3 {
-                            min[c] = min[c].min(tester[c]);
-                            max[c] = max[c].max(tester[c]);
-                        }
-                    }
-                }
-            }
+// An instance whose transform is interpolated between keyframes at the time
+// of each ray, so that any object can be motion blurred.
+pub struct AnimatedTransform {
+    instance: HittableInstance,
+    keyframes: Vec<Keyframe>,
+}
 
-            Some(aabb(min, max))
-        } else {
-            None
-        };
+impl AnimatedTransform {
+    pub fn new(instance: HittableInstance, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "AnimatedTransform needs at least one keyframe"
+        );
+
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
 
         Self {
             instance,
-            bbox,
-            sin_theta,
-            cos_theta,
+            keyframes,
+        }
+    }
+
+    // The pose at `time`, holding the first and last keyframes outside of
+    // the animated range.
+    pub fn pose(&self, time: Float) -> Keyframe {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+
+        if next == 0 {
+            return Keyframe {
+                time,
+                ..self.keyframes[0]
+            };
+        }
+        if next == self.keyframes.len() {
+            return Keyframe {
+                time,
+                ..self.keyframes[next - 1]
+            };
+        }
+
+        let k0 = &self.keyframes[next - 1];
+        let k1 = &self.keyframes[next];
+        let t = (time - k0.time) / (k1.time - k0.time);
+
+        Keyframe {
+            time,
+            translation: k0.translation + t * (k1.translation - k0.translation),
+            rotation: k0.rotation.slerp(&k1.rotation, t),
+            scale: k0.scale + t * (k1.scale - k0.scale),
         }
     }
 }
 
-impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let mut origin = r.origin;
-        let mut direction = r.direction;
+impl Hittable for AnimatedTransform {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let pose = self.pose(r.time);
 
-        origin.x = self.cos_theta * r.origin.x - self.sin_theta * r.origin.z;
-        origin.z = self.sin_theta * r.origin.x + self.cos_theta * r.origin.z;
+        transformed_hit(
+            &self.instance,
+            &pose.matrix(),
+            &pose.inverse_matrix(),
+            r,
+            t_min,
+            t_max,
+        )
+    }
 
-        direction.x = self.cos_theta * r.direction.x - self.sin_theta * r.direction.z;
-        direction.z = self.sin_theta * r.direction.x + self.cos_theta * r.direction.z;
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        let inverse = self.pose(r.time).inverse_matrix();
+        self.instance
+            .occluded(&local_ray(&inverse, r), t_min, t_max)
+    }
 
-        let rotated_r = Ray::new(origin, direction, r.time);
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
+        let bbox = self.instance.bounding_box(time0, time1)?;
 
-        if let Some(rec) = self.instance.hit(&rotated_r, t_min, t_max) {
-            let mut p = rec.p;
-            let mut normal = rec.normal;
+        // Split the shutter interval at every keyframe, so that each span
+        // rotates at a constant rate.
+        let mut times = vec![time0, time1];
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|k| k.time)
+                .filter(|&t| time0 < t && t < time1),
+        );
+        times.sort_by(Float::total_cmp);
 
-            p.x = self.cos_theta * rec.p.x + self.sin_theta * rec.p.z;
-            p.z = -self.sin_theta * rec.p.x + self.cos_theta * rec.p.z;
+        // Distance of the furthest corner from the center of rotation.
+        let radius = vec3(
+            Float::max(bbox.min.x.abs(), bbox.max.x.abs()),
+            Float::max(bbox.min.y.abs(), bbox.max.y.abs()),
+            Float::max(bbox.min.z.abs(), bbox.max.z.abs()),
+        )
+        .length();
 
-            normal.x = self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.z;
-            normal.z = -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.z;
+        let mut output_box = bbox.transformed(&self.pose(time0).matrix());
+        let mut padding: Float = 0.0;
 
-            let (face_normal, front_face) = face_normal_and_is_front(&rotated_r, normal);
+        for span in times.windows(2) {
+            // A point rotating between two samples strays from the chord by
+            // at most r * (1 - cos(step / 2)).
+            let (start, end) = (self.pose(span[0]), self.pose(span[1]));
+            let step = start.rotation.angle_to(&end.rotation) / MOTION_STEPS as Float;
+            let stray = radius * (1.0 - (step / 2.0).cos());
 
-            Some(HitRecord {
-                normal: face_normal,
-                p,
-                front_face,
-                ..rec
+            for i in 1..=MOTION_STEPS {
+                let time = span[0] + (span[1] - span[0]) * i as Float / MOTION_STEPS as Float;
+                let pose = self.pose(time);
+                let scale =
+                    Float::max(pose.scale.x.abs(), pose.scale.y.abs()).max(pose.scale.z.abs());
+
+                output_box = AABB::surrounding_box(&output_box, &bbox.transformed(&pose.matrix()));
+                padding = padding.max(scale * stray);
+            }
+        }
+
+        let padding = vec3(padding, padding, padding);
+
+        Some(aabb(output_box.min - padding, output_box.max + padding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh_node::*;
+    use crate::quad::*;
+    use crate::sphere::*;
+
+    // Directions spread over the sphere, without randomness.
+    fn directions() -> impl Iterator<Item = Vec3> {
+        (0..500).map(|i| {
+            let f = i as Float;
+            vec3((f * 0.37).sin(), (f * 0.73).cos(), (f * 1.31).sin() + 0.1).unit()
+        })
+    }
+
+    #[test]
+    fn spawned_rays_do_not_hit_their_own_surface() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let far = point3(1000.0, 2000.0, -3000.0);
+
+        let sphere: HittableInstance = Rc::new(Sphere::new(far, 0.5, material.clone()));
+        let (u, v) = (vec3(2.0, 0.1, 0.0), vec3(0.0, 2.0, 0.3));
+        let quad: HittableInstance =
+            Rc::new(Quad::new(far - 0.5 * u - 0.5 * v, u, v, material.clone()));
+        let scaled: HittableInstance = Rc::new(Transform::new(
+            Rc::new(Sphere::new(point3(0.0, 0.0, 0.0), 1.0, material)),
+            Mat4::translation(far) * Mat4::scale(vec3(0.3, 0.7, 0.5)),
+        ));
+
+        for (object, convex) in [(sphere, true), (quad, false), (scaled, true)] {
+            for d in directions() {
+                let r = Ray::new(far - 10.0 * d, d, 0.0);
+                let rec = object.hit(&r, 0.0, Float::INFINITY).unwrap();
+
+                for w in directions() {
+                    // Leaving a convex shape, or either side of a flat one,
+                    // there is nothing else to hit.
+                    if w.dot(&rec.normal) > 0.0 || !convex {
+                        let spawned = rec.spawn_ray(w, 0.0);
+                        assert!(object.hit(&spawned, 0.0, Float::INFINITY).is_none());
+                        assert!(!object.occluded(&spawned, 0.0, Float::INFINITY));
+                    }
+                }
+            }
+        }
+    }
+
+    // f32 numbers are 6 cm apart out there, so only f64 can place the ray.
+    #[cfg(feature = "f64")]
+    #[test]
+    fn resolves_small_spheres_far_from_the_origin() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        // A sphere of 1 cm, 1000 km out, hit 5 mm to the side of its center.
+        let center = point3(1.0e6, 0.0, 0.0);
+        let sphere = Sphere::new(center, 0.01, material);
+        let r = Ray::new(center + vec3(0.005, 0.0, 1.0), vec3(0.0, 0.0, -1.0), 0.0);
+
+        let rec = sphere.hit(&r, 0.0, Float::INFINITY).unwrap();
+        let expected = vec3(0.005, 0.0, (0.01 * 0.01 - 0.005 * 0.005 as Float).sqrt());
+        let error = (rec.p - center - expected).length();
+
+        // f64 numbers are a fraction of a nanometer apart there.
+        assert!(error < 1e-8, "{}", error);
+    }
+
+    #[test]
+    fn instances_share_a_prototype() {
+        let gray: Rc<dyn Material> = Rc::new(DiffuseLight::new(color(0.5, 0.5, 0.5)));
+        let red: Rc<dyn Material> = Rc::new(DiffuseLight::new(color(1.0, 0.0, 0.0)));
+
+        let balls: Vec<HittableInstance> = (0..10)
+            .map(|i| -> HittableInstance {
+                let center = point3(i as Float, 0.0, 0.0);
+                Rc::new(Sphere::new(center, 0.25, gray.clone()))
+            })
+            .collect();
+        let prototype: HittableInstance = Rc::new(BVHNode::new(&balls, 0.0, 1.0));
+
+        // A row of copies of the row, every other one in red.
+        let copies: Vec<HittableInstance> = (0..100)
+            .map(|i| -> HittableInstance {
+                let copy = Transform::new(
+                    prototype.clone(),
+                    Mat4::translation(vec3(0.0, i as Float, 0.0)),
+                );
+                if i % 2 == 0 {
+                    Rc::new(copy)
+                } else {
+                    Rc::new(copy.with_material(red.clone()))
+                }
             })
-        } else {
-            None
+            .collect();
+        let world = BVHNode::new(&copies, 0.0, 1.0);
+
+        for i in [0, 1, 50, 99] {
+            let r = Ray::new(point3(3.0, i as Float, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+            let rec = world.hit(&r, 0.0, Float::INFINITY).unwrap();
+
+            assert!((rec.t - 4.75).abs() < 1e-4);
+            let expected = if i % 2 == 0 { 0.5 } else { 1.0 };
+            assert_eq!(rec.material.emitted(0.0, 0.0, &rec.p).x, expected);
+        }
+        assert_eq!(Rc::strong_count(&prototype), 101);
+    }
+
+    // A unit cube off to the side of the origin, spun half way round the
+    // y axis and lifted over [0, 1], and stretched then squashed in x.
+    fn spinning_cube() -> AnimatedTransform {
+        use crate::cube::*;
+
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let cube = Rc::new(Cube::new(
+            point3(0.5, -0.5, -0.5),
+            point3(1.5, 0.5, 0.5),
+            material,
+        ));
+        let up = vec3(0.0, 1.0, 0.0);
+
+        AnimatedTransform::new(
+            cube,
+            vec![
+                Keyframe::new(
+                    1.0,
+                    vec3(0.0, 2.0, 0.0),
+                    Quat::from_axis_angle(up, 180.0),
+                    vec3(1.0, 1.0, 1.0),
+                ),
+                Keyframe::new(
+                    0.0,
+                    vec3(0.0, 0.0, 0.0),
+                    Quat::IDENTITY,
+                    vec3(1.0, 1.0, 1.0),
+                ),
+                Keyframe::new(
+                    0.5,
+                    vec3(0.0, 1.0, 0.0),
+                    Quat::from_axis_angle(up, 90.0),
+                    vec3(2.0, 1.0, 1.0),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn interpolates_between_keyframes() {
+        let cube = spinning_cube();
+        let near = |a: Vec3, b: Vec3| (a - b).length() < 1e-5;
+        let x = vec3(1.0, 0.0, 0.0);
+
+        // A quarter of the way from the first keyframe to the second.
+        let pose = cube.pose(0.125);
+        assert!(near(pose.translation, vec3(0.0, 0.25, 0.0)));
+        assert!(near(pose.scale, vec3(1.25, 1.0, 1.0)));
+        let angle = pose.rotation.angle_to(&Quat::IDENTITY).to_degrees();
+        assert!((angle - 22.5).abs() < 1e-3, "{}", angle);
+        let a = (22.5 as Float).to_radians();
+        assert!(near(pose.rotation.rotate(x), vec3(a.cos(), 0.0, -a.sin())));
+
+        // Between the second and third, slerped rather than lerped.
+        let pose = cube.pose(0.75);
+        assert!(near(pose.translation, vec3(0.0, 1.5, 0.0)));
+        assert!(near(pose.scale, vec3(1.5, 1.0, 1.0)));
+        let angle = pose.rotation.angle_to(&Quat::IDENTITY).to_degrees();
+        assert!((angle - 135.0).abs() < 1e-3, "{}", angle);
+        assert!((pose.rotation.length() - 1.0).abs() < 1e-5);
+
+        // Held at the ends outside of the animated range.
+        for (time, first) in [(-1.0, true), (0.0, true), (1.0, false), (7.0, false)] {
+            let pose = cube.pose(time);
+            let (translation, angle) = if first { (0.0, 0.0) } else { (2.0, 180.0) };
+            assert_eq!(pose.time, time);
+            assert!(near(pose.translation, vec3(0.0, translation, 0.0)));
+            assert!(near(pose.scale, vec3(1.0, 1.0, 1.0)));
+            let actual = pose.rotation.angle_to(&Quat::IDENTITY).to_degrees();
+            assert!((actual - angle).abs() < 1e-2, "{} at {}", actual, time);
+        }
+    }
+
+    #[test]
+    fn hits_a_spinning_cube_where_its_pose_is() {
+        let cube = spinning_cube();
+        let shutter = cube.bounding_box(0.0, 1.0).unwrap();
+
+        for time in [0.0, 0.1, 0.3, 0.5, 0.64, 0.9, 1.0] {
+            let pose = cube.pose(time);
+            let still = Transform::new(cube.instance.clone(), pose.matrix());
+
+            for d in directions() {
+                let r = Ray::new(point3(0.0, pose.translation.y, 0.0) - 6.0 * d, d, time);
+                let moving = cube.hit(&r, 0.0, Float::INFINITY);
+                let expected = still.hit(&r, 0.0, Float::INFINITY);
+
+                assert_eq!(
+                    moving.is_some(),
+                    expected.is_some(),
+                    "along {:?} at {}",
+                    d,
+                    time
+                );
+                if let (Some(a), Some(b)) = (moving, expected) {
+                    assert!((a.t - b.t).abs() < 1e-4, "{} != {} at {}", a.t, b.t, time);
+                    assert!((a.normal - b.normal).length() < 1e-4);
+                }
+            }
+        }
+
+        // Every pose over the shutter interval lies within its box, not only
+        // the ones it was sampled at.
+        for i in 0..=1000 {
+            let time = i as Float / 1000.0;
+            let still = Transform::new(cube.instance.clone(), cube.pose(time).matrix());
+            let bbox = still.bounding_box(time, time).unwrap();
+
+            for a in 0..3 {
+                assert!(
+                    shutter.min[a] <= bbox.min[a] + 1e-5,
+                    "{:?} at {}",
+                    bbox,
+                    time
+                );
+                assert!(
+                    bbox.max[a] <= shutter.max[a] + 1e-5,
+                    "{:?} at {}",
+                    bbox,
+                    time
+                );
+            }
         }
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
-        self.bbox
+    #[test]
+    fn occluded_agrees_with_hit() {
+        use crate::capsule::*;
+        use crate::cone::*;
+        use crate::constant_medium::*;
+        use crate::csg::*;
+        use crate::cube::*;
+        use crate::cylinder::*;
+        use crate::disk::*;
+        use crate::heightfield::*;
+        use crate::plane::*;
+        use crate::rect::*;
+        use crate::sdf::*;
+        use crate::torus::*;
+        use crate::triangle::*;
+        use crate::voxel_grid::*;
+
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let m = || material.clone();
+        let o = point3(0.0, 0.0, 0.0);
+        let up = vec3(0.0, 1.0, 0.0);
+        let cube = || -> HittableInstance {
+            Rc::new(Cube::new(
+                -o - vec3(1.0, 1.0, 1.0),
+                o + vec3(1.0, 1.0, 1.0),
+                m(),
+            ))
+        };
+
+        let heights = (0..16).map(|i| (i as Float * 0.7).sin()).collect();
+        let mut voxels = VoxelGrid::sparse(point3(-1.0, -1.0, -1.0), 0.5, [4, 4, 4], vec![m()]);
+        for cell in [[0, 0, 0], [1, 2, 3], [2, 2, 2], [3, 1, 0]] {
+            voxels.set(cell, 1);
+        }
+
+        let objects: Vec<(&str, HittableInstance)> = vec![
+            ("sphere", Rc::new(Sphere::new(o, 1.0, m()))),
+            ("xy", Rc::new(XY::new(-1.0, 1.0, -0.5, 0.5, 0.2, m()))),
+            ("xz", Rc::new(XZ::new(-1.0, 1.0, -0.5, 0.5, 0.2, m()))),
+            ("yz", Rc::new(YZ::new(-1.0, 1.0, -0.5, 0.5, 0.2, m()))),
+            (
+                "quad",
+                Rc::new(Quad::new(o, vec3(1.0, 0.2, 0.0), vec3(0.0, 0.5, 1.0), m())),
+            ),
+            (
+                "polygon",
+                Rc::new(Polygon::new(
+                    vec![
+                        o,
+                        point3(1.0, 0.0, 0.0),
+                        point3(1.0, 1.0, 0.5),
+                        point3(0.0, 1.0, 0.0),
+                    ],
+                    m(),
+                )),
+            ),
+            (
+                "triangle",
+                Rc::new(Triangle::new(
+                    o,
+                    point3(1.0, 0.0, 0.0),
+                    point3(0.0, 1.0, 1.0),
+                    m(),
+                )),
+            ),
+            ("disk", Rc::new(Disk::new(o, vec3(0.3, 1.0, 0.2), 1.0, m()))),
+            (
+                "annulus",
+                Rc::new(Disk::annulus(o, vec3(0.3, 1.0, 0.2), 0.5, 1.0, m())),
+            ),
+            (
+                "plane",
+                Rc::new(InfinitePlane::new(o, vec3(0.2, 1.0, 0.1), m())),
+            ),
+            (
+                "heightfield",
+                Rc::new(HeightField::new(
+                    point3(-1.0, 0.0, -1.0),
+                    2.0,
+                    2.0,
+                    4,
+                    4,
+                    heights,
+                    m(),
+                )),
+            ),
+            (
+                "capsule",
+                Rc::new(Capsule::new(
+                    point3(-0.5, -0.5, 0.0),
+                    point3(0.5, 0.5, 0.2),
+                    0.4,
+                    m(),
+                )),
+            ),
+            (
+                "cylinder",
+                Rc::new(Cylinder::new(-up, 0.8, 2.0, m()).with_sweep(4.0)),
+            ),
+            (
+                "uncapped cylinder",
+                Rc::new(Cylinder::new(-up, 0.8, 2.0, m()).uncapped()),
+            ),
+            (
+                "cone",
+                Rc::new(Cone::new(-up, 0.8, 2.0, m()).with_sweep(4.0)),
+            ),
+            (
+                "uncapped cone",
+                Rc::new(Cone::new(-up, 0.8, 2.0, m()).uncapped()),
+            ),
+            ("torus", Rc::new(Torus::new(o, 1.0, 0.3, m()))),
+            (
+                "sdf",
+                Rc::new(SdfShape::new(
+                    Rc::new(SdfSphere { radius: 1.0 }),
+                    cube().bounding_box(0.0, 1.0).unwrap(),
+                    m(),
+                )),
+            ),
+            ("voxel grid", Rc::new(voxels)),
+            (
+                "csg",
+                Rc::new(Csg::difference(cube(), Rc::new(Sphere::new(o, 1.2, m())))),
+            ),
+            (
+                "dense fog",
+                Rc::new(ConstantMedium::with_color(
+                    cube(),
+                    1e6,
+                    color(1.0, 1.0, 1.0),
+                )),
+            ),
+            (
+                "thin fog",
+                Rc::new(ConstantMedium::with_color(
+                    cube(),
+                    1e-9,
+                    color(1.0, 1.0, 1.0),
+                )),
+            ),
+        ];
+
+        for (name, object) in &objects {
+            let mut hits = 0;
+
+            // From outside towards points around the middle, and from
+            // inside out, stopping short, in the middle and past everything.
+            for (i, d) in directions().enumerate() {
+                let target = 0.3 * vec3((i as Float).cos(), (i as Float * 0.5).sin(), 0.0);
+                let from_inside = i % 5 == 0;
+                let origin = if from_inside {
+                    target
+                } else {
+                    target - 3.0 * d
+                };
+
+                for t_max in [0.5, 2.5, 3.0, Float::INFINITY] {
+                    let r = Ray::new(origin, d, 0.0);
+                    let hit = object.hit(&r, 0.0, t_max).is_some();
+                    hits += hit as usize;
+
+                    assert_eq!(
+                        object.occluded(&r, 0.0, t_max),
+                        hit,
+                        "{} along {:?} to {}",
+                        name,
+                        d,
+                        t_max
+                    );
+                }
+            }
+
+            if *name != "thin fog" {
+                assert!(hits > 0, "{} was never hit", name);
+            }
+        }
     }
 }
@@ -1,7 +1,9 @@
 use crate::aabb::*;
 use crate::hittable::*;
 use crate::ray::*;
+use crate::vec3::*;
 
+#[derive(Default)]
 pub struct HittableList {
     pub objects: Vec<HittableInstance>,
 }
@@ -23,7 +25,7 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
         let mut hit_anything: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
 
@@ -37,8 +39,14 @@ impl Hittable for HittableList {
         hit_anything
     }
 
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.objects
+            .iter()
+            .any(|object| object.occluded(r, t_min, t_max))
+    }
+
     // TODO: Find a way to accomplish this without the mutable state.
-    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
         if self.objects.is_empty() {
             return None;
         }
@@ -0,0 +1,402 @@
+use crate::aabb::*;
+use crate::accelerator::*;
+use crate::hittable::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+// Costs for the surface area heuristic, relative to each other, and the
+// discount for splits that leave one side empty.
+const TRAVERSAL_COST: Float = 1.0;
+const INTERSECTION_COST: Float = 80.0;
+const EMPTY_BONUS: Float = 0.5;
+
+const MAX_LEAF_SIZE: usize = 1;
+const STACK_SIZE: usize = 64;
+
+// A kd-tree built with the surface area heuristic.  Unlike a BVH it splits
+// space rather than objects, so an object may be referenced from several
+// leaves, but a ray can stop at the first leaf with a hit.
+pub struct KdTree {
+    nodes: Vec<KdNode>,
+    // The objects of each leaf, as indices into `objects`.
+    indices: Vec<u32>,
+    objects: Vec<HittableInstance>,
+    bounds: AABB,
+    // Objects without a bounding box are tested on every ray.
+    unbounded: Vec<HittableInstance>,
+    build_time: Duration,
+    counters: TraversalCounters,
+}
+
+// The first child of an interior node is the next node in the array.
+#[derive(Copy, Clone, Debug)]
+struct KdNode {
+    split: Float,
+    // The split axis, or LEAF.
+    axis: u8,
+    // The second child of an interior node, or the first index of a leaf.
+    offset: u32,
+    // The number of objects in a leaf.
+    count: u32,
+}
+
+const LEAF: u8 = 3;
+
+#[derive(Copy, Clone, Debug)]
+struct Edge {
+    t: Float,
+    object: u32,
+    start: bool,
+}
+
+impl KdTree {
+    pub fn new(objects: &[HittableInstance], time0: Float, time1: Float) -> Self {
+        let start = Instant::now();
+        let (bounded, unbounded) = split_bounded(objects, time0, time1);
+
+        let boxes: Vec<AABB> = bounded.iter().map(|(_, bbox)| *bbox).collect();
+        let inf = Float::INFINITY;
+        let bounds = boxes
+            .iter()
+            .copied()
+            .reduce(|a, b| AABB::surrounding_box(&a, &b))
+            .unwrap_or_else(|| aabb(point3(inf, inf, inf), point3(-inf, -inf, -inf)));
+
+        let mut tree = Self {
+            nodes: Vec::new(),
+            indices: Vec::new(),
+            objects: bounded.into_iter().map(|(object, _)| object).collect(),
+            bounds,
+            unbounded,
+            build_time: Duration::ZERO,
+            counters: TraversalCounters::default(),
+        };
+
+        if !boxes.is_empty() {
+            let max_depth = (8.0 + 1.3 * (boxes.len() as Float).log2()).round() as usize;
+            let all = (0..boxes.len() as u32).collect();
+
+            tree.build(bounds, all, &boxes, max_depth, 0);
+        }
+
+        tree.build_time = start.elapsed();
+        tree
+    }
+
+    fn build(
+        &mut self,
+        bounds: AABB,
+        objects: Vec<u32>,
+        boxes: &[AABB],
+        depth: usize,
+        bad_refines: usize,
+    ) {
+        let leaf_cost = INTERSECTION_COST * objects.len() as Float;
+
+        if objects.len() <= MAX_LEAF_SIZE || depth == 0 {
+            self.leaf(objects);
+            return;
+        }
+
+        let Some((axis, split, cost)) = best_split(&bounds, &objects, boxes) else {
+            self.leaf(objects);
+            return;
+        };
+
+        // Allow a few splits that look worse than a leaf, as later splits
+        // can still pay off.
+        let bad_refines = bad_refines + (cost > leaf_cost) as usize;
+        if (cost > 4.0 * leaf_cost && objects.len() < 16) || bad_refines == 3 {
+            self.leaf(objects);
+            return;
+        }
+
+        let below: Vec<u32> = objects
+            .iter()
+            .copied()
+            .filter(|&o| {
+                let b = &boxes[o as usize];
+                b.min[axis] < split || b.max[axis] <= split
+            })
+            .collect();
+        let above: Vec<u32> = objects
+            .iter()
+            .copied()
+            .filter(|&o| boxes[o as usize].max[axis] > split)
+            .collect();
+
+        let mut below_bounds = bounds;
+        below_bounds.max[axis] = split;
+        let mut above_bounds = bounds;
+        above_bounds.min[axis] = split;
+
+        let index = self.nodes.len();
+        self.nodes.push(KdNode {
+            split,
+            axis: axis as u8,
+            offset: 0,
+            count: 0,
+        });
+
+        self.build(below_bounds, below, boxes, depth - 1, bad_refines);
+        self.nodes[index].offset = self.nodes.len() as u32;
+        self.build(above_bounds, above, boxes, depth - 1, bad_refines);
+    }
+
+    fn leaf(&mut self, objects: Vec<u32>) {
+        self.nodes.push(KdNode {
+            split: 0.0,
+            axis: LEAF,
+            offset: self.indices.len() as u32,
+            count: objects.len() as u32,
+        });
+        self.indices.extend(objects);
+    }
+
+    // Pass the objects of each leaf the ray goes through within [t_min,
+    // t_max] to `visit`, nearest first.  It returns the t of the closest hit
+    // so far, and the walk stops at the first leaf beyond it.
+    fn walk<F: FnMut(&[u32]) -> Float>(&self, r: &Ray, t_min: Float, t_max: Float, mut visit: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let (mut t0, mut t1) = match self.bounds.clip(r, t_min, t_max) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut closest_t = t_max;
+
+        let inv_dir = vec3(
+            1.0 / r.direction.x,
+            1.0 / r.direction.y,
+            1.0 / r.direction.z,
+        );
+
+        let mut stack: [(usize, Float, Float); STACK_SIZE] = [(0, 0.0, 0.0); STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            // Everything left is further away than the closest hit.
+            if closest_t < t0 {
+                break;
+            }
+
+            let node = &self.nodes[current];
+            self.counters.node();
+
+            if node.axis != LEAF {
+                let axis = node.axis as usize;
+                let t_plane = (node.split - r.origin[axis]) * inv_dir[axis];
+
+                // The child on the side the span starts in comes first.
+                let p0 = r.origin[axis] + t0 * r.direction[axis];
+                let below_first = p0 < node.split || (p0 == node.split && r.direction[axis] <= 0.0);
+                let (first, second) = if below_first {
+                    (current + 1, node.offset as usize)
+                } else {
+                    (node.offset as usize, current + 1)
+                };
+
+                if t_plane > t1 || t_plane <= t0 || t_plane.is_nan() {
+                    current = first;
+                } else {
+                    stack[stack_len] = (second, t_plane, t1);
+                    stack_len += 1;
+                    current = first;
+                    t1 = t_plane;
+                }
+                continue;
+            }
+
+            let first = node.offset as usize;
+            let objects = &self.indices[first..first + node.count as usize];
+            self.counters.primitives(objects.len());
+
+            closest_t = visit(objects);
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            (current, t0, t1) = stack[stack_len];
+        }
+    }
+}
+
+// The axis, position and cost of the cheapest split plane through the edges
+// of the objects' boxes.
+fn best_split(bounds: &AABB, objects: &[u32], boxes: &[AABB]) -> Option<(usize, Float, Float)> {
+    let extent = bounds.max - bounds.min;
+    let inv_area = 1.0 / bounds.surface_area();
+    let mut best: Option<(usize, Float, Float)> = None;
+
+    for axis in 0..3 {
+        let mut edges: Vec<Edge> = objects
+            .iter()
+            .flat_map(|&o| {
+                let b = &boxes[o as usize];
+                [
+                    Edge {
+                        t: b.min[axis],
+                        object: o,
+                        start: true,
+                    },
+                    Edge {
+                        t: b.max[axis],
+                        object: o,
+                        start: false,
+                    },
+                ]
+            })
+            .collect();
+
+        // At the same position, starts come before ends.
+        edges.sort_by(|a, b| {
+            a.t.total_cmp(&b.t)
+                .then(b.start.cmp(&a.start))
+                .then(a.object.cmp(&b.object))
+        });
+
+        // The areas of the faces perpendicular to the split, and the
+        // perimeter of the rest.
+        let (o1, o2) = ((axis + 1) % 3, (axis + 2) % 3);
+        let cap_area = 2.0 * extent[o1] * extent[o2];
+        let side = 2.0 * (extent[o1] + extent[o2]);
+
+        let mut below = 0;
+        let mut above = objects.len();
+
+        for edge in &edges {
+            if !edge.start {
+                above -= 1;
+            }
+
+            let t = edge.t;
+            if t > bounds.min[axis] && t < bounds.max[axis] {
+                let p_below = (cap_area + side * (t - bounds.min[axis])) * inv_area;
+                let p_above = (cap_area + side * (bounds.max[axis] - t)) * inv_area;
+                let bonus = if below == 0 || above == 0 {
+                    EMPTY_BONUS
+                } else {
+                    0.0
+                };
+
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (1.0 - bonus)
+                        * (p_below * below as Float + p_above * above as Float);
+
+                if best.is_none_or(|b| cost < b.2) {
+                    best = Some((axis, t, cost));
+                }
+            }
+
+            if edge.start {
+                below += 1;
+            }
+        }
+    }
+
+    best
+}
+
+impl Hittable for KdTree {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.counters.ray();
+        self.counters.primitives(self.unbounded.len());
+
+        let mut rec: Option<HitRecord> = None;
+        let mut closest_t = t_max;
+
+        for object in &self.unbounded {
+            if let Some(r) = object.hit(r, t_min, closest_t) {
+                closest_t = r.t;
+                rec = Some(r);
+            }
+        }
+
+        self.walk(r, t_min, closest_t, |objects| {
+            for &object in objects {
+                if let Some(r) = self.objects[object as usize].hit(r, t_min, closest_t) {
+                    closest_t = r.t;
+                    rec = Some(r);
+                }
+            }
+
+            closest_t
+        });
+
+        rec
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.counters.ray();
+        self.counters.primitives(self.unbounded.len());
+
+        if self
+            .unbounded
+            .iter()
+            .any(|object| object.occluded(r, t_min, t_max))
+        {
+            return true;
+        }
+
+        let mut occluded = false;
+        self.walk(r, t_min, t_max, |objects| {
+            occluded = objects
+                .iter()
+                .any(|&object| self.objects[object as usize].occluded(r, t_min, t_max));
+
+            // Stop at the first hit.
+            if occluded {
+                -Float::INFINITY
+            } else {
+                t_max
+            }
+        });
+
+        occluded
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        Some(self.bounds)
+    }
+}
+
+impl Accelerator for KdTree {
+    fn name(&self) -> &'static str {
+        "kdtree"
+    }
+
+    fn stats(&self) -> AcceleratorStats {
+        let memory = self.nodes.len() * size_of::<KdNode>()
+            + self.indices.len() * size_of::<u32>()
+            + (self.objects.len() + self.unbounded.len()) * size_of::<HittableInstance>();
+
+        self.counters
+            .stats(self.build_time, memory, self.nodes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accelerator::tests::*;
+
+    #[test]
+    fn matches_a_plain_list() {
+        let objects = clumpy_objects();
+        let accelerator = KdTree::new(&objects, 0.0, 1.0);
+
+        assert_matches_a_plain_list(&accelerator, &objects);
+    }
+}
@@ -1,40 +1,87 @@
 pub mod aabb;
+pub mod accelerator;
+pub mod bvh4;
 pub mod bvh_node;
 pub mod camera;
+pub mod capsule;
+pub mod cone;
 pub mod constant_medium;
+pub mod csg;
 pub mod cube;
+pub mod cylinder;
+pub mod disk;
+pub mod gltf_scene;
+pub mod grid;
+pub mod heightfield;
 pub mod hittable;
 pub mod hittable_list;
+pub mod kd_tree;
+pub mod mat4;
 pub mod material;
+pub mod mesh;
 pub mod perlin;
+pub mod plane;
+pub mod ply;
+pub mod quad;
+pub mod quat;
+pub mod query;
 pub mod ray;
 pub mod rect;
+pub mod scatter;
+pub mod scene_graph;
 pub mod scenes;
+pub mod sdf;
 pub mod sphere;
 pub mod texture;
+pub mod torus;
+pub mod triangle;
 pub mod vec3;
+pub mod voxel_grid;
 
 // Re-export all the public traits, structs, methods.
 pub use crate::aabb::*;
+pub use crate::accelerator::*;
+pub use crate::bvh4::*;
 pub use crate::bvh_node::*;
 pub use crate::camera::*;
+pub use crate::capsule::*;
+pub use crate::cone::*;
 pub use crate::constant_medium::*;
+pub use crate::csg::*;
 pub use crate::cube::*;
+pub use crate::cylinder::*;
+pub use crate::disk::*;
+pub use crate::grid::*;
+pub use crate::heightfield::*;
 pub use crate::hittable::*;
 pub use crate::hittable_list::*;
+pub use crate::kd_tree::*;
+pub use crate::mat4::*;
 pub use crate::material::*;
+pub use crate::mesh::*;
 pub use crate::perlin::*;
+pub use crate::plane::*;
+pub use crate::ply::*;
+pub use crate::quad::*;
+pub use crate::quat::*;
+pub use crate::query::*;
 pub use crate::ray::*;
+pub use crate::scatter::*;
+pub use crate::scene_graph::*;
+pub use crate::sdf::*;
 pub use crate::sphere::*;
 pub use crate::texture::*;
+pub use crate::torus::*;
+pub use crate::triangle::*;
 pub use crate::vec3::*;
+pub use crate::voxel_grid::*;
 
-pub fn random() -> f32 {
-    rand::random::<f32>()
+pub fn random() -> Float {
+    rand::random::<Float>()
 }
 
 #[inline]
-pub fn random_range(min: f32, max: f32) -> f32 {
+pub fn random_range(min: Float, max: Float) -> Float {
     // Returns a random real in [min,max).
     min + (max - min) * random()
 }
@@ -1,13 +1,10 @@
 use rtweekend::*;
+use std::env;
 use std::io;
 
 fn main() -> io::Result<()> {
-    // const ASPECT_RATIO: f32 = 16.0 / 9.0;
-    const ASPECT_RATIO: f32 = 1.0;
-
     // Image
     const IMAGE_WIDTH: u32 = 800;
-    const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f32 / ASPECT_RATIO) as u32;
     const MAX_DEPTH: i32 = 50;
 
     // let scene = scenes::test();
@@ -17,46 +14,77 @@ fn main() -> io::Result<()> {
     // let scene = scenes::two_perlin_spheres();
     // let scene = scenes::earth();
     // let scene = scenes::simple_light();
+    // let scene = scenes::primitives();
+    // let scene = scenes::csg();
     // let scene = scenes::cornell_box();
     // let scene = scenes::cornell_smoke();
-    let scene = scenes::final_scene_the_next_week();
+    // let scene = scenes::voxels();
+    let mut scene = scenes::final_scene_the_next_week();
+
+    // Options
+    let mut object_ids = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--accelerator" => {
+                let kind = args.next().unwrap_or_default();
+                scene.accelerator = kind
+                    .parse()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            }
+            "--object-ids" => object_ids = true,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown option '{}'", arg),
+                ))
+            }
+        }
+    }
+
+    // The height follows the aspect ratio of the scene.
+    let image_height = (IMAGE_WIDTH as Float / scene.aspect_ratio) as u32;
 
     // Camera
-    const VUP: Vec3 = vec3(0.0, 1.0, 0.0);
-    const DIST_TO_FOCUS: f32 = 10.0;
+    const DIST_TO_FOCUS: Float = 10.0;
 
     let camera = Camera::new(
         scene.lookfrom,
         scene.lookat,
-        VUP,
+        scene.vup,
         scene.vfov,
-        ASPECT_RATIO,
+        scene.aspect_ratio,
         scene.aperture,
         DIST_TO_FOCUS,
         0.0,
         1.0,
     );
 
-    // Convert the world objects into a BVH
-    let world_scene = BVHNode::new(scene.world.as_slice(), 0.0, 1.0);
+    if object_ids {
+        write_object_ids(&scene, &camera, IMAGE_WIDTH, image_height);
+        return Ok(());
+    }
+
+    // Convert the world objects into an acceleration structure
+    let world_scene = build_accelerator(scene.accelerator, scene.world.as_slice(), 0.0, 1.0);
 
     println!("P3");
-    println!("{} {}", IMAGE_WIDTH, IMAGE_HEIGHT);
+    println!("{} {}", IMAGE_WIDTH, image_height);
     println!("255");
 
-    for j in (0..IMAGE_HEIGHT).rev() {
+    for j in (0..image_height).rev() {
         eprint!("\rScanlines remaining: {} ", j);
 
         for i in 0..IMAGE_WIDTH {
             let mut pixel_color = color(0.0, 0.0, 0.0);
 
             for _ in 0..(scene.samples_per_pixel) {
-                let u = (i as f32 + random()) / ((IMAGE_WIDTH - 1) as f32);
-                let v = (j as f32 + random()) / ((IMAGE_HEIGHT - 1) as f32);
+                let u = (i as Float + random()) / ((IMAGE_WIDTH - 1) as Float);
+                let v = (j as Float + random()) / ((image_height - 1) as Float);
 
                 let r = camera.get_ray(u, v);
 
-                pixel_color += ray_color(r, scene.background, &world_scene, MAX_DEPTH);
+                pixel_color += ray_color(r, scene.background, world_scene.as_ref(), MAX_DEPTH);
             }
 
             write_color(pixel_color, scene.samples_per_pixel);
@@ -64,6 +92,7 @@ fn main() -> io::Result<()> {
     }
 
     eprintln!("\nDone.");
+    eprintln!("{}: {}", world_scene.name(), world_scene.stats());
 
     Ok(())
 }
@@ -73,8 +102,10 @@ fn ray_color(r: Ray, background: Color, world: &dyn Hittable, depth: i32) -> Col
         return color(0.0, 0.0, 0.0);
     }
 
-    if let Some(rec) = world.hit(&r, 0.001, f32::INFINITY) {
-        let emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
+    // Scattered rays start off the surface by its error bounds, so nothing
+    // nearer than the origin needs to be skipped.
+    if let Some(rec) = world.hit(&r, 0.0, Float::INFINITY) {
+        let emitted = rec.material.emitted_at(&rec);
 
         if let Some((attenuation, scattered)) = rec.material.scatter(&r, &rec) {
             emitted + attenuation * ray_color(scattered, background, world, depth - 1)
@@ -92,7 +123,7 @@ fn ray_color(r: Ray, background: Color, world: &dyn Hittable, depth: i32) -> Col
 //     let mut c = color(1.0, 1.0, 1.0);
 
 //     for _ in 0..depth {
-//         if let Some(rec) = world.hit(&r, 0.001, f32::INFINITY) {
+//         if let Some(rec) = world.hit(&r, 0.001, Float::INFINITY) {
 //             let emitted = rec.material.emitted(rec.u, rec.v, &rec.p);
 
 //             if let Some((attenuation, scattered)) = rec.material.scatter(&r, &rec) {
@@ -110,13 +141,43 @@ fn ray_color(r: Ray, background: Color, world: &dyn Hittable, depth: i32) -> Col
 //     color(0.0, 0.0, 0.0)
 // }
 
+// An image of which object is seen through each pixel, each in a color of
+// its own, with the background black.
+fn write_object_ids(scene: &scenes::Scene, camera: &Camera, width: u32, height: u32) {
+    let query = GeometryQuery::from_scene(scene);
+
+    println!("P3");
+    println!("{} {}", width, height);
+    println!("255");
+
+    for y in 0..height {
+        for x in 0..width {
+            match query.pick(camera, x, y, width, height) {
+                Some(pick) => {
+                    // Spread consecutive ids far apart in color.
+                    let hash = (pick.object + 1).wrapping_mul(2654435761);
+                    println!(
+                        "{} {} {}",
+                        hash >> 24,
+                        (hash >> 16) & 255,
+                        (hash >> 8) & 255
+                    );
+                }
+                None => println!("0 0 0"),
+            }
+        }
+    }
+
+    eprintln!("{} objects, {} named", scene.world.len(), scene.names.len());
+}
+
 fn write_color(pixel_color: Color, samples_per_pixel: i32) {
     let r = pixel_color.x;
     let g = pixel_color.y;
     let b = pixel_color.z;
 
     // Divide the color by the number of samples and gamma-correct for gamma=2.0
-    let scale = 1.0 / (samples_per_pixel as f32);
+    let scale = 1.0 / (samples_per_pixel as Float);
 
     let rs = (r * scale).sqrt();
     let gs = (g * scale).sqrt();
@@ -124,9 +185,9 @@ fn write_color(pixel_color: Color, samples_per_pixel: i32) {
 
     // Scale the 0..1 into 0..255
     // TODO: This is not a perfect scaling
-    let ir = (256.0 * f32::clamp(rs, 0.0, 0.999)) as u32;
-    let ig = (256.0 * f32::clamp(gs, 0.0, 0.999)) as u32;
-    let ib = (256.0 * f32::clamp(bs, 0.0, 0.999)) as u32;
+    let ir = (256.0 * Float::clamp(rs, 0.0, 0.999)) as u32;
+    let ig = (256.0 * Float::clamp(gs, 0.0, 0.999)) as u32;
+    let ib = (256.0 * Float::clamp(bs, 0.0, 0.999)) as u32;
 
     println!("{} {} {}", ir, ig, ib);
 }
@@ -0,0 +1,306 @@
+use crate::quat::*;
+use crate::vec3::*;
+
+// A 4x4 matrix stored in row major order, acting on column vectors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[Float; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    #[inline]
+    pub const fn new(m: [[Float; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    // Build from the column major layout used by glTF and OpenGL.
+    pub fn from_cols(cols: [[Float; 4]; 4]) -> Self {
+        Self::new(cols).transpose()
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotation by `angle` degrees counter-clockwise about `axis`.
+    pub fn rotation(axis: Vec3, angle: Float) -> Self {
+        Self::from_quat(Quat::from_axis_angle(axis, angle))
+    }
+
+    #[inline]
+    pub fn rotation_x(angle: Float) -> Self {
+        Self::rotation(vec3(1.0, 0.0, 0.0), angle)
+    }
+
+    #[inline]
+    pub fn rotation_y(angle: Float) -> Self {
+        Self::rotation(vec3(0.0, 1.0, 0.0), angle)
+    }
+
+    #[inline]
+    pub fn rotation_z(angle: Float) -> Self {
+        Self::rotation(vec3(0.0, 0.0, 1.0), angle)
+    }
+
+    // Shear where e.g. `xy` is how much x moves per unit of y.
+    pub fn shear(xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Self {
+        Self::new([
+            [1.0, xy, xz, 0.0],
+            [yx, 1.0, yz, 0.0],
+            [zx, zy, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn from_quat(q: Quat) -> Self {
+        let q = q.normalize();
+        let (w, x, y, z) = (q.w, q.v.x, q.v.y, q.v.z);
+
+        Self::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Self::IDENTITY;
+
+        for i in 0..4 {
+            for j in 0..4 {
+                t.m[i][j] = self.m[j][i];
+            }
+        }
+
+        t
+    }
+
+    // Gauss-Jordan elimination with partial pivoting.  Returns None when the
+    // matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Self::new(inv))
+    }
+
+    #[inline]
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        point3(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    // Transform a direction, ignoring the translation.
+    #[inline]
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        vec3(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    // A bound on the error of transform_point(p), where p itself is only
+    // known to within `p_error`.
+    pub fn transform_point_error(&self, p: Point3, p_error: Vec3) -> Vec3 {
+        let m = &self.m;
+        let mut error = vec3(0.0, 0.0, 0.0);
+
+        for (i, row) in m.iter().take(3).enumerate() {
+            let terms = (row[0] * p.x).abs() + (row[1] * p.y).abs() + (row[2] * p.z).abs();
+            let carried =
+                row[0].abs() * p_error.x + row[1].abs() * p_error.y + row[2].abs() * p_error.z;
+
+            error[i] = (1.0 + gamma(3)) * carried + gamma(3) * (terms + row[3].abs());
+        }
+
+        error
+    }
+
+    // Normals transform by the inverse transpose, so this must be called on
+    // the inverse of the matrix that transforms the points.
+    #[inline]
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.m;
+        vec3(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl std::ops::Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut r = Mat4::new([[0.0; 4]; 4]);
+
+        for i in 0..4 {
+            for j in 0..4 {
+                r.m[i][j] = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn translates_points_not_vectors() {
+        let t = Mat4::translation(vec3(1.0, 2.0, 3.0));
+
+        assert_eq!(
+            t.transform_point(point3(1.0, 1.0, 1.0)),
+            point3(2.0, 3.0, 4.0)
+        );
+        assert_eq!(t.transform_vector(vec3(1.0, 1.0, 1.0)), vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rotates_counter_clockwise() {
+        let r = Mat4::rotation_y(90.0);
+
+        // Same convention as the book's rotate_y
+        assert_near(
+            r.transform_vector(vec3(1.0, 0.0, 0.0)),
+            vec3(0.0, 0.0, -1.0),
+        );
+        assert_near(
+            Mat4::rotation_z(90.0).transform_vector(vec3(1.0, 0.0, 0.0)),
+            vec3(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn multiplication_applies_right_to_left() {
+        let m = Mat4::translation(vec3(1.0, 0.0, 0.0)) * Mat4::scale(vec3(2.0, 2.0, 2.0));
+
+        assert_eq!(
+            m.transform_point(point3(1.0, 1.0, 1.0)),
+            point3(3.0, 2.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let m = Mat4::translation(vec3(1.0, -2.0, 3.0))
+            * Mat4::rotation(vec3(1.0, 1.0, 0.0), 33.0)
+            * Mat4::shear(0.5, 0.0, 0.0, 0.25, 0.0, 0.0)
+            * Mat4::scale(vec3(2.0, 3.0, 0.5));
+
+        let inv = m.inverse().unwrap();
+        let p = point3(0.3, -0.7, 1.9);
+
+        assert_near(inv.transform_point(m.transform_point(p)), p);
+        assert_near((m * inv).transform_point(p), p);
+    }
+
+    #[test]
+    fn singular_has_no_inverse() {
+        assert!(Mat4::scale(vec3(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let m = Mat4::scale(vec3(4.0, 1.0, 1.0)) * Mat4::rotation_z(30.0);
+        let inv = m.inverse().unwrap();
+
+        let tangent = vec3(1.0, -1.0, 0.0);
+        let normal = vec3(1.0, 1.0, 0.0);
+
+        let t = m.transform_vector(tangent);
+        let n = inv.transform_normal(normal);
+
+        assert!(t.dot(&n).abs() < 1e-5);
+    }
+}
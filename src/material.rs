@@ -8,9 +8,15 @@ pub type Scattered = (Color, Ray);
 pub trait Material {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scattered>;
 
-    fn emitted(&self, _u: f32, _v: f32, _p: &Point3) -> Color {
+    fn emitted(&self, _u: Float, _v: Float, _p: &Point3) -> Color {
         color(0.0, 0.0, 0.0)
     }
+
+    // The light emitted at a hit.  Materials whose emission comes from a
+    // texture override this, so the texture sees the whole hit.
+    fn emitted_at(&self, rec: &HitRecord) -> Color {
+        self.emitted(rec.u, rec.v, &rec.p)
+    }
 }
 
 pub struct Lambertian {
@@ -41,19 +47,23 @@ impl Material for Lambertian {
         };
 
         Some((
-            self.albedo.value(rec.u, rec.v, &rec.p),
-            Ray::new(rec.p, choosen_scattered_direction, r_in.time),
+            self.albedo.value_at(rec),
+            rec.spawn_ray(choosen_scattered_direction, r_in.time),
         ))
     }
 }
 
 pub struct Metal {
-    pub albedo: Color,
-    pub fuzz: f32,
+    pub albedo: Rc<dyn Texture>,
+    pub fuzz: Float,
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f32) -> Metal {
+    pub fn new(albedo: Color, fuzz: Float) -> Metal {
+        Self::new_from_texture(Rc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn new_from_texture(albedo: Rc<dyn Texture>, fuzz: Float) -> Metal {
         Metal {
             albedo,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
@@ -65,12 +75,8 @@ impl Material for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scattered> {
         let reflected = r_in.direction.unit().reflect(&rec.normal);
 
-        let scattered = Ray::new(
-            rec.p,
-            reflected + self.fuzz * random_in_unit_sphere(),
-            r_in.time,
-        );
-        let attenuation = self.albedo;
+        let scattered = rec.spawn_ray(reflected + self.fuzz * random_in_unit_sphere(), r_in.time);
+        let attenuation = self.albedo.value_at(rec);
 
         if scattered.direction.dot(&rec.normal) > 0.0 {
             Some((attenuation, scattered))
@@ -81,11 +87,11 @@ impl Material for Metal {
 }
 
 pub struct Dielectric {
-    pub ir: f32,
+    pub ir: Float,
 }
 
 impl Dielectric {
-    pub fn new(ir: f32) -> Dielectric {
+    pub fn new(ir: Float) -> Dielectric {
         Dielectric { ir }
     }
 }
@@ -100,7 +106,7 @@ impl Material for Dielectric {
 
         let unit_direction = r_in.direction.unit();
 
-        let cos_theta = f32::min(-unit_direction.dot(&rec.normal), 1.0);
+        let cos_theta = Float::min(-unit_direction.dot(&rec.normal), 1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
@@ -116,7 +122,7 @@ impl Material for Dielectric {
             unit_direction.refract(&rec.normal, refraction_ratio)
         };
 
-        Some((color(1.0, 1.0, 1.0), Ray::new(rec.p, direction, r_in.time)))
+        Some((color(1.0, 1.0, 1.0), rec.spawn_ray(direction, r_in.time)))
     }
 }
 
@@ -141,9 +147,13 @@ impl Material for DiffuseLight {
         None
     }
 
-    fn emitted(&self, u: f32, v: f32, p: &Point3) -> Color {
+    fn emitted(&self, u: Float, v: Float, p: &Point3) -> Color {
         self.emit.value(u, v, p)
     }
+
+    fn emitted_at(&self, rec: &HitRecord) -> Color {
+        self.emit.value_at(rec)
+    }
 }
 
 pub struct Isotropic {
@@ -164,8 +174,8 @@ impl Isotropic {
 
 impl Material for Isotropic {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scattered> {
-        let color = self.albedo.value(rec.u, rec.v, &rec.p);
-        let scattered = Ray::new(rec.p, random_in_unit_sphere(), r_in.time);
+        let color = self.albedo.value_at(rec);
+        let scattered = rec.spawn_ray(random_in_unit_sphere(), r_in.time);
 
         Some((color, scattered))
     }
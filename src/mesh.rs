@@ -0,0 +1,271 @@
+use crate::aabb::*;
+use crate::bvh_node::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::texture::*;
+use crate::triangle;
+use crate::vec3::*;
+
+use std::rc::Rc;
+
+// Shared vertex and index buffers of a triangle mesh.  The normals and uvs
+// are optional: they are either empty or have one entry per position.
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(Float, Float)>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+pub struct Mesh {
+    triangles: BVHNode,
+}
+
+impl Mesh {
+    pub fn new(data: MeshData, material: Rc<dyn Material>) -> Self {
+        Self::build(Rc::new(data), material)
+    }
+
+    // Build a mesh whose surface color is interpolated from the per-vertex
+    // colors.  `make_material` wraps the color texture, shared by the whole
+    // mesh, into a material, e.g.
+    // `|t| Rc::new(Lambertian::new_from_texture(t))`.
+    //
+    // NOTE: The vertex color texture is indexed by the barycentric
+    // coordinates of the hit, so any uvs in `data` are ignored.
+    pub fn with_vertex_colors<F>(mut data: MeshData, colors: &[Color], make_material: F) -> Self
+    where
+        F: Fn(Rc<dyn Texture>) -> Rc<dyn Material>,
+    {
+        data.uvs.clear();
+
+        let data = Rc::new(data);
+        let texture = VertexColorTexture::new(data.clone(), colors.to_vec());
+
+        Self::build(data, make_material(Rc::new(texture)))
+    }
+
+    fn build(data: Rc<MeshData>, material: Rc<dyn Material>) -> Self {
+        assert!(
+            !data.indices.is_empty(),
+            "Cannot build a mesh without any faces"
+        );
+
+        let triangles: Vec<HittableInstance> = (0..data.indices.len())
+            .map(|index| -> HittableInstance {
+                Rc::new(MeshTriangle {
+                    data: data.clone(),
+                    index,
+                    material: material.clone(),
+                })
+            })
+            .collect();
+
+        Self {
+            triangles: BVHNode::new(triangles.as_slice(), 0.0, 1.0),
+        }
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.triangles.hit(r, t_min, t_max)
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.triangles.occluded(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
+        self.triangles.bounding_box(time0, time1)
+    }
+}
+
+struct MeshTriangle {
+    data: Rc<MeshData>,
+    index: usize,
+    material: Rc<dyn Material>,
+}
+
+impl MeshTriangle {
+    #[inline]
+    fn vertices(&self) -> (Point3, Point3, Point3) {
+        let [i0, i1, i2] = self.data.indices[self.index];
+        let positions = &self.data.positions;
+
+        (positions[i0], positions[i1], positions[i2])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (v0, v1, v2) = self.vertices();
+        let (t, b1, b2) = triangle::intersect(r, v0, v1, v2, t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let [i0, i1, i2] = self.data.indices[self.index];
+
+        let geometric_normal = (v1 - v0).cross(&(v2 - v0)).unit();
+        let outward_normal = if self.data.normals.is_empty() {
+            geometric_normal
+        } else {
+            let n = &self.data.normals;
+            (b0 * n[i0] + b1 * n[i1] + b2 * n[i2]).unit()
+        };
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        // Without texture coordinates, expose the barycentric coordinates so
+        // that textures such as VertexColorTexture can interpolate.
+        let (u, v) = if self.data.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let uv = &self.data.uvs;
+            (
+                b0 * uv[i0].0 + b1 * uv[i1].0 + b2 * uv[i2].0,
+                b0 * uv[i0].1 + b1 * uv[i1].1 + b2 * uv[i2].1,
+            )
+        };
+
+        let (p, p_error) = triangle::barycentric_point(v0, v1, v2, b1, b2);
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: self.index as u32,
+            mesh: Some(&self.data),
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        let (v0, v1, v2) = self.vertices();
+        triangle::intersect(r, v0, v1, v2, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        let (v0, v1, v2) = self.vertices();
+        Some(triangle::bounding_box(v0, v1, v2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down(x: Float, y: Float) -> Ray {
+        Ray::new(point3(x, y, 1.0), vec3(0.0, 0.0, -1.0), 0.0)
+    }
+
+    #[test]
+    fn interpolates_vertex_colors_across_each_face() {
+        // A unit square of two faces, red, green, blue and white at the
+        // corners.
+        let data = MeshData {
+            positions: vec![
+                point3(0.0, 0.0, 0.0),
+                point3(1.0, 0.0, 0.0),
+                point3(1.0, 1.0, 0.0),
+                point3(0.0, 1.0, 0.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        };
+        let colors = [
+            color(1.0, 0.0, 0.0),
+            color(0.0, 1.0, 0.0),
+            color(0.0, 0.0, 1.0),
+            color(1.0, 1.0, 1.0),
+        ];
+        let mesh = Mesh::with_vertex_colors(data, &colors, |t| {
+            Rc::new(DiffuseLight::new_from_texture(t))
+        });
+
+        let emitted = |x: Float, y: Float| {
+            let rec = mesh.hit(&down(x, y), 0.0, Float::INFINITY).unwrap();
+            rec.material.emitted_at(&rec)
+        };
+
+        // At the corners of each face, and halfway along the edge of the
+        // second one.
+        assert!((emitted(0.999, 0.001) - color(0.0, 1.0, 0.0)).length() < 0.01);
+        assert!((emitted(0.001, 0.999) - color(1.0, 1.0, 1.0)).length() < 0.01);
+        assert!((emitted(0.5, 0.999) - color(0.5, 0.5, 1.0)).length() < 0.01);
+    }
+
+    #[test]
+    fn shares_vertex_colors_only_with_its_own_faces() {
+        use crate::sphere::*;
+        use std::cell::RefCell;
+
+        let square = || MeshData {
+            positions: vec![
+                point3(0.0, 0.0, 0.0),
+                point3(1.0, 0.0, 0.0),
+                point3(1.0, 1.0, 0.0),
+                point3(0.0, 1.0, 0.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        };
+        let red = color(1.0, 0.0, 0.0);
+
+        // A single triangle, keeping hold of its material to put it on
+        // other things too.
+        let mut triangle = square();
+        triangle.indices.truncate(1);
+        let shared = RefCell::new(None);
+        let mesh = Mesh::with_vertex_colors(triangle, &[red; 4], |t| {
+            let material: Rc<dyn Material> = Rc::new(DiffuseLight::new_from_texture(t));
+            *shared.borrow_mut() = Some(material.clone());
+            material
+        });
+        let material = shared.into_inner().unwrap();
+
+        let rec = mesh.hit(&down(0.75, 0.25), 0.0, Float::INFINITY).unwrap();
+        assert_eq!(rec.material.emitted_at(&rec), red);
+
+        // A sphere has no face, and face 1 of another mesh is not one of
+        // the triangle's.
+        let sphere = Sphere::new(point3(0.5, 0.5, 0.0), 0.25, material.clone());
+        let other = Mesh::new(square(), material);
+        for object in [&sphere as &dyn Hittable, &other] {
+            let rec = object.hit(&down(0.4, 0.6), 0.0, Float::INFINITY).unwrap();
+            assert_eq!(rec.material.emitted_at(&rec), color(0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn hits_tiny_triangles() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let size = 1e-5;
+        let data = MeshData {
+            positions: vec![
+                point3(0.0, 0.0, 0.0),
+                point3(size, 0.0, 0.0),
+                point3(0.0, size, 0.0),
+            ],
+            indices: vec![[0, 1, 2]],
+            ..MeshData::default()
+        };
+        let mesh = Mesh::new(data, material);
+
+        let rec = mesh
+            .hit(&down(0.25 * size, 0.25 * size), 0.0, Float::INFINITY)
+            .unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-5);
+        assert!(mesh.hit(&down(size, size), 0.0, Float::INFINITY).is_none());
+
+        // Still missed when parallel to it.
+        let along = Ray::new(point3(-1.0, 0.1 * size, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(mesh.hit(&along, 0.0, Float::INFINITY).is_none());
+    }
+}
@@ -28,10 +28,10 @@ impl Perlin {
         }
     }
 
-    pub fn turb(&self, p: &Point3, depth: u32) -> f32 {
-        let mut accum: f32 = 0.0;
+    pub fn turb(&self, p: &Point3, depth: u32) -> Float {
+        let mut accum: Float = 0.0;
         let mut tp = *p;
-        let mut weight: f32 = 1.0;
+        let mut weight: Float = 1.0;
 
         for _ in 0..depth {
             accum += weight * self.noise(&tp);
@@ -42,7 +42,7 @@ impl Perlin {
         accum.abs()
     }
 
-    pub fn noise(&self, p: &Point3) -> f32 {
+    pub fn noise(&self, p: &Point3) -> Float {
         let u = p.x - p.x.floor();
         let v = p.y - p.y.floor();
         let w = p.z - p.z.floor();
@@ -81,17 +81,17 @@ impl Perlin {
         let ww = w * w * (3.0 - 2.0 * w);
 
         // Perlin interpolation
-        let mut accum: f32 = 0.0;
+        let mut accum: Float = 0.0;
 
         for i in 0..2 {
             for j in 0..2 {
                 for k in 0..2 {
-                    let weight_v = vec3(u - i as f32, v - j as f32, w - k as f32);
+                    let weight_v = vec3(u - i as Float, v - j as Float, w - k as Float);
                     let rv = c[idx(i, j, k)];
 
-                    accum += (i as f32 * uu + (1.0 - i as f32) * (1.0 - uu))
-                        * (j as f32 * vv + (1.0 - j as f32) * (1.0 - vv))
-                        * (k as f32 * ww + (1.0 - k as f32) * (1.0 - ww))
+                    accum += (i as Float * uu + (1.0 - i as Float) * (1.0 - uu))
+                        * (j as Float * vv + (1.0 - j as Float) * (1.0 - vv))
+                        * (k as Float * ww + (1.0 - k as Float) * (1.0 - ww))
                         * rv.dot(&weight_v);
                 }
             }
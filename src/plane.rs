@@ -0,0 +1,82 @@
+use crate::aabb::*;
+use crate::disk::basis;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::rc::Rc;
+
+// A plane through `point` facing `normal`, extending forever.  It has no
+// bounding box, so accelerators test it on every ray.  The uvs tile once per
+// unit along the plane.
+pub struct InfinitePlane {
+    point: Point3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Rc<dyn Material>,
+}
+
+impl InfinitePlane {
+    pub fn new(point: Point3, normal: Vec3, material: Rc<dyn Material>) -> Self {
+        let normal = normal.unit();
+        let (tangent, bitangent) = basis(normal);
+
+        Self {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+
+    // The t of the hit, if it is within range.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let denom = self.normal.dot(&r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.point - r.origin).dot(&self.normal) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        Some(t)
+    }
+}
+
+impl Hittable for InfinitePlane {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let t = self.intersect(r, t_min, t_max)?;
+
+        let p = r.at(t);
+        let offset = p - self.point;
+        let (normal, front_face) = face_normal_and_is_front(r, self.normal);
+
+        Some(HitRecord {
+            p,
+            p_error: ray_point_error(r, t),
+            normal,
+            geometric_normal: self.normal,
+            material: &*self.material,
+            t,
+            u: offset.dot(&self.tangent).rem_euclid(1.0),
+            v: offset.dot(&self.bitangent).rem_euclid(1.0),
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        None
+    }
+}
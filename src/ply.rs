@@ -0,0 +1,615 @@
+use crate::material::*;
+use crate::mesh::*;
+use crate::texture::*;
+use crate::vec3::*;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::rc::Rc;
+
+// A triangle mesh loaded from a Stanford PLY file.  Polygons with more than
+// three vertices are triangulated as fans.  The normals, uvs and colors are
+// either empty or have one entry per position.
+#[derive(Default)]
+pub struct Ply {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(Float, Float)>,
+    pub colors: Vec<Color>,
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl Ply {
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        Self::parse(BufReader::new(file))
+    }
+
+    pub fn parse<R: BufRead>(mut reader: R) -> io::Result<Self> {
+        let header = Header::parse(&mut reader)?;
+
+        let ply = match header.format {
+            Format::Ascii => {
+                let mut body = String::new();
+                reader.read_to_string(&mut body)?;
+
+                let mut source = AsciiSource {
+                    tokens: body.split_whitespace(),
+                };
+                Self::parse_body(&header, &mut source)?
+            }
+            Format::BinaryLittleEndian | Format::BinaryBigEndian => {
+                let mut source = BinarySource {
+                    reader,
+                    big_endian: header.format == Format::BinaryBigEndian,
+                };
+                Self::parse_body(&header, &mut source)?
+            }
+        };
+
+        if let Some(index) = ply
+            .faces
+            .iter()
+            .flatten()
+            .find(|&&i| i >= ply.positions.len())
+        {
+            return Err(invalid(format!("face references missing vertex {}", index)));
+        }
+
+        Ok(ply)
+    }
+
+    pub fn into_mesh(self, material: Rc<dyn Material>) -> io::Result<Mesh> {
+        self.check_faces()?;
+        Ok(Mesh::new(self.mesh_data(), material))
+    }
+
+    // Use the per-vertex colors as the texture of the mesh.  See
+    // `Mesh::with_vertex_colors`: the colors are interpolated with the
+    // barycentric coordinates of each hit, so any uvs in the file are
+    // dropped.
+    pub fn into_vertex_color_mesh<F>(mut self, make_material: F) -> io::Result<Mesh>
+    where
+        F: Fn(Rc<dyn Texture>) -> Rc<dyn Material>,
+    {
+        self.check_faces()?;
+        if self.colors.len() != self.positions.len() {
+            return Err(invalid("vertices have no colors".to_string()));
+        }
+
+        let colors = std::mem::take(&mut self.colors);
+        Ok(Mesh::with_vertex_colors(
+            self.mesh_data(),
+            &colors,
+            make_material,
+        ))
+    }
+
+    // A point cloud, or any other file without faces, has nothing to hit.
+    fn check_faces(&self) -> io::Result<()> {
+        if self.faces.is_empty() {
+            return Err(invalid("no faces to build a mesh from".to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn mesh_data(self) -> MeshData {
+        MeshData {
+            positions: self.positions,
+            normals: self.normals,
+            uvs: self.uvs,
+            indices: self.faces,
+        }
+    }
+
+    fn parse_body(header: &Header, source: &mut dyn Source) -> io::Result<Self> {
+        let mut ply = Ply::default();
+
+        for element in &header.elements {
+            match element.name.as_str() {
+                "vertex" => ply.parse_vertices(element, source)?,
+                "face" => ply.parse_faces(element, source)?,
+                _ => {
+                    for _ in 0..element.count {
+                        for property in &element.properties {
+                            property.skip(source)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ply)
+    }
+
+    fn parse_vertices(&mut self, element: &Element, source: &mut dyn Source) -> io::Result<()> {
+        let slot = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+        };
+
+        let position = [slot(&["x"]), slot(&["y"]), slot(&["z"])];
+        let normal = [slot(&["nx"]), slot(&["ny"]), slot(&["nz"])];
+        let uv = [
+            slot(&["u", "s", "texture_u", "texture_s"]),
+            slot(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let rgb = [
+            slot(&["red", "diffuse_red"]),
+            slot(&["green", "diffuse_green"]),
+            slot(&["blue", "diffuse_blue"]),
+        ];
+
+        if position.iter().any(Option::is_none) {
+            return Err(invalid("vertex element is missing x, y or z".to_string()));
+        }
+
+        let has_normals = normal.iter().all(Option::is_some);
+        let has_uvs = uv.iter().all(Option::is_some);
+        let has_colors = rgb.iter().all(Option::is_some);
+
+        let mut values = vec![0.0; element.properties.len()];
+
+        for _ in 0..element.count {
+            for (value, property) in values.iter_mut().zip(&element.properties) {
+                *value = match property.kind {
+                    PropertyKind::Scalar(ty) => source.read(ty)?,
+                    PropertyKind::List { .. } => {
+                        property.skip(source)?;
+                        0.0
+                    }
+                };
+            }
+
+            let get = |slot: Option<usize>| values[slot.unwrap()] as Float;
+            let get_color = |slot: Option<usize>| {
+                let index = slot.unwrap();
+                // Integer channels span their full range, floats are in [0,1]
+                let max = match element.properties[index].kind {
+                    PropertyKind::Scalar(ScalarType::U8) => 255.0,
+                    PropertyKind::Scalar(ScalarType::U16) => 65535.0,
+                    _ => 1.0,
+                };
+                values[index] as Float / max
+            };
+
+            self.positions
+                .push(point3(get(position[0]), get(position[1]), get(position[2])));
+
+            if has_normals {
+                self.normals
+                    .push(vec3(get(normal[0]), get(normal[1]), get(normal[2])));
+            }
+
+            if has_uvs {
+                self.uvs.push((get(uv[0]), get(uv[1])));
+            }
+
+            if has_colors {
+                self.colors.push(color(
+                    get_color(rgb[0]),
+                    get_color(rgb[1]),
+                    get_color(rgb[2]),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_faces(&mut self, element: &Element, source: &mut dyn Source) -> io::Result<()> {
+        let indices = element
+            .properties
+            .iter()
+            .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")
+            .ok_or_else(|| invalid("face element has no vertex_indices".to_string()))?;
+
+        let mut polygon: Vec<usize> = Vec::new();
+
+        for _ in 0..element.count {
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::List { count, item } if i == indices => {
+                        let n = source.read(count)? as usize;
+
+                        polygon.clear();
+                        for _ in 0..n {
+                            let index = source.read(item)?;
+                            if index < 0.0 {
+                                return Err(invalid(format!("negative vertex index {}", index)));
+                            }
+                            polygon.push(index as usize);
+                        }
+
+                        for k in 1..n.saturating_sub(1) {
+                            self.faces.push([polygon[0], polygon[k], polygon[k + 1]]);
+                        }
+                    }
+                    _ => property.skip(source)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> io::Result<Self> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::I8),
+            "uchar" | "uint8" => Ok(ScalarType::U8),
+            "short" | "int16" => Ok(ScalarType::I16),
+            "ushort" | "uint16" => Ok(ScalarType::U16),
+            "int" | "int32" => Ok(ScalarType::I32),
+            "uint" | "uint32" => Ok(ScalarType::U32),
+            "float" | "float32" => Ok(ScalarType::F32),
+            "double" | "float64" => Ok(ScalarType::F64),
+            _ => Err(invalid(format!("unknown property type {}", name))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+impl Property {
+    fn skip(&self, source: &mut dyn Source) -> io::Result<()> {
+        match self.kind {
+            PropertyKind::Scalar(ty) => {
+                source.read(ty)?;
+            }
+            PropertyKind::List { count, item } => {
+                let n = source.read(count)? as usize;
+                for _ in 0..n {
+                    source.read(item)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    fn parse<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let mut line = String::new();
+
+        reader.read_line(&mut line)?;
+        if line.trim_end() != "ply" {
+            return Err(invalid("missing ply magic number".to_string()));
+        }
+
+        let mut format: Option<Format> = None;
+        let mut elements: Vec<Element> = Vec::new();
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid("unexpected end of header".to_string()));
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words.as_slice() {
+                ["end_header"] => break,
+                [] | ["comment", ..] | ["obj_info", ..] => {}
+                ["format", name, _version] => {
+                    format = Some(match *name {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        _ => return Err(invalid(format!("unknown format {}", name))),
+                    });
+                }
+                ["element", name, count] => {
+                    let count = count
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid element count {}", count)))?;
+
+                    elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    });
+                }
+                ["property", "list", count, item, name] => {
+                    let kind = PropertyKind::List {
+                        count: ScalarType::parse(count)?,
+                        item: ScalarType::parse(item)?,
+                    };
+                    Self::push_property(&mut elements, name, kind)?;
+                }
+                ["property", ty, name] => {
+                    let kind = PropertyKind::Scalar(ScalarType::parse(ty)?);
+                    Self::push_property(&mut elements, name, kind)?;
+                }
+                _ => return Err(invalid(format!("unexpected header line {}", line.trim()))),
+            }
+        }
+
+        let format = format.ok_or_else(|| invalid("missing format line".to_string()))?;
+
+        Ok(Self { format, elements })
+    }
+
+    fn push_property(elements: &mut [Element], name: &str, kind: PropertyKind) -> io::Result<()> {
+        let element = elements
+            .last_mut()
+            .ok_or_else(|| invalid(format!("property {} outside of an element", name)))?;
+
+        element.properties.push(Property {
+            name: name.to_string(),
+            kind,
+        });
+
+        Ok(())
+    }
+}
+
+// Yields the scalar values of the body one at a time, regardless of the
+// encoding of the file.
+trait Source {
+    fn read(&mut self, ty: ScalarType) -> io::Result<f64>;
+}
+
+struct AsciiSource<'a> {
+    tokens: std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> Source for AsciiSource<'a> {
+    fn read(&mut self, _ty: ScalarType) -> io::Result<f64> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+        token
+            .parse()
+            .map_err(|_| invalid(format!("invalid number {}", token)))
+    }
+}
+
+struct BinarySource<R> {
+    reader: R,
+    big_endian: bool,
+}
+
+impl<R: Read> Source for BinarySource<R> {
+    fn read(&mut self, ty: ScalarType) -> io::Result<f64> {
+        let mut bytes = [0u8; 8];
+        let bytes = &mut bytes[..ty.size()];
+
+        self.reader.read_exact(bytes)?;
+
+        if self.big_endian != cfg!(target_endian = "big") {
+            bytes.reverse();
+        }
+
+        let value = match ty {
+            ScalarType::I8 => i8::from_ne_bytes([bytes[0]]) as f64,
+            ScalarType::U8 => bytes[0] as f64,
+            ScalarType::I16 => i16::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+            ScalarType::U16 => u16::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+            ScalarType::I32 => i32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+            ScalarType::U32 => u32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+            ScalarType::F32 => f32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+            ScalarType::F64 => f64::from_ne_bytes(bytes.try_into().unwrap()),
+        };
+
+        Ok(value)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINARY_HEADER: &str = "element vertex 3
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let mut bytes = format!("ply\nformat {} 1.0\n{}", format, BINARY_HEADER).into_bytes();
+
+        let vertices = [
+            (0.0f32, 0.0f32, 0.0f32, 255u8),
+            (1.0, 0.0, 0.0, 0),
+            (0.0, 1.0, 0.0, 51),
+        ];
+
+        for (x, y, z, red) in vertices {
+            for value in [x, y, z] {
+                if big_endian {
+                    bytes.extend(value.to_be_bytes());
+                } else {
+                    bytes.extend(value.to_le_bytes());
+                }
+            }
+            bytes.extend([red, 0, 0]);
+        }
+
+        bytes.push(3);
+        for index in [0i32, 1, 2] {
+            if big_endian {
+                bytes.extend(index.to_be_bytes());
+            } else {
+                bytes.extend(index.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn parses_ascii() {
+        let source = "ply
+format ascii 1.0
+comment a unit square
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+1 1 0 0 0 1 1 1
+0 1 0 0 0 1 0 1
+4 0 1 2 3
+";
+
+        let ply = Ply::parse(source.as_bytes()).unwrap();
+
+        assert_eq!(ply.positions.len(), 4);
+        assert_eq!(ply.positions[2], point3(1.0, 1.0, 0.0));
+        assert_eq!(ply.normals[3], vec3(0.0, 0.0, 1.0));
+        assert_eq!(ply.uvs[1], (1.0, 0.0));
+        assert!(ply.colors.is_empty());
+        assert_eq!(ply.faces, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn parses_binary_little_endian() {
+        let ply = Ply::parse(binary("binary_little_endian", false).as_slice()).unwrap();
+
+        assert_eq!(ply.positions[1], point3(1.0, 0.0, 0.0));
+        assert_eq!(ply.colors[0], color(1.0, 0.0, 0.0));
+        assert_eq!(ply.colors[2], color(0.2, 0.0, 0.0));
+        assert_eq!(ply.faces, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn parses_binary_big_endian() {
+        let ply = Ply::parse(binary("binary_big_endian", true).as_slice()).unwrap();
+
+        assert_eq!(ply.positions[2], point3(0.0, 1.0, 0.0));
+        assert_eq!(ply.colors[0], color(1.0, 0.0, 0.0));
+        assert_eq!(ply.faces, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let source = "ply
+format ascii 1.0
+element vertex 1
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+3 0 1 2
+";
+
+        assert!(Ply::parse(source.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn builds_meshes_only_from_faces_and_colors() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let make_material = |t| -> Rc<dyn Material> { Rc::new(Lambertian::new_from_texture(t)) };
+        let points = "ply
+format ascii 1.0
+element vertex 2
+property float x
+property float y
+property float z
+end_header
+0 0 0
+1 0 0
+";
+        let error = Ply::parse(points.as_bytes())
+            .unwrap()
+            .into_mesh(material.clone())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Faces but no colors.
+        let square = Ply::parse(binary("binary_little_endian", false).as_slice()).unwrap();
+        let no_colors = Ply {
+            colors: Vec::new(),
+            ..square
+        };
+        let error = no_colors
+            .into_vertex_color_mesh(make_material)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let ply = Ply::parse(binary("binary_little_endian", false).as_slice()).unwrap();
+        assert!(ply.into_vertex_color_mesh(make_material).is_ok());
+        let ply = Ply::parse(binary("binary_little_endian", false).as_slice()).unwrap();
+        assert!(ply.into_mesh(material).is_ok());
+    }
+}
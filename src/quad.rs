@@ -0,0 +1,353 @@
+use crate::aabb::*;
+use crate::disk::basis;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::rc::Rc;
+
+const PADDING: Float = 0.0001;
+
+// A parallelogram with one corner at `q` and edges `u` and `v`.  The front
+// face is on the side of u x v.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    d: Float,
+    // n / (n . n), used to find the planar coordinates of a hit.
+    w: Vec3,
+    material: Rc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Rc<dyn Material>) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit();
+
+        Self {
+            q,
+            u,
+            v,
+            normal,
+            d: normal.dot(&q),
+            w: n / n.length_squared(),
+            material,
+        }
+    }
+
+    // The t of the hit and its coordinates in the (u, v) frame of the plane.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
+        let t = plane_hit(r, self.normal, self.d, t_min, t_max)?;
+
+        let planar = r.at(t) - self.q;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some((t, alpha, beta))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, alpha, beta) = self.intersect(r, t_min, t_max)?;
+        let p = r.at(t);
+
+        let (normal, front_face) = face_normal_and_is_front(r, self.normal);
+
+        Some(HitRecord {
+            p,
+            p_error: ray_point_error(r, t),
+            normal,
+            geometric_normal: self.normal,
+            material: &*self.material,
+            t,
+            u: alpha,
+            v: beta,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(points_bounding_box(&[
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ]))
+    }
+}
+
+// A planar, convex polygon, intersected as a fan of triangles around its
+// first vertex.  The front face is on the side the vertices wind
+// counter-clockwise around.  The uvs span the bounding rectangle of the
+// polygon in its plane, with u along the first edge.
+pub struct Polygon {
+    vertices: Vec<Point3>,
+    normal: Vec3,
+    d: Float,
+    tangent: Vec3,
+    bitangent: Vec3,
+    uv_min: (Float, Float),
+    uv_size: (Float, Float),
+    material: Rc<dyn Material>,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Point3>, material: Rc<dyn Material>) -> Self {
+        assert!(vertices.len() >= 3, "A polygon needs at least 3 vertices");
+
+        // Newell's method is robust to collinear leading vertices.
+        let mut n = vec3(0.0, 0.0, 0.0);
+        for (i, a) in vertices.iter().enumerate() {
+            let b = vertices[(i + 1) % vertices.len()];
+            n += vec3(
+                (a.y - b.y) * (a.z + b.z),
+                (a.z - b.z) * (a.x + b.x),
+                (a.x - b.x) * (a.y + b.y),
+            );
+        }
+        let normal = n.unit();
+
+        let first_edge = vertices[1] - vertices[0];
+        let (tangent, bitangent) = if first_edge.near_zero() {
+            basis(normal)
+        } else {
+            let tangent = first_edge.unit();
+            (tangent, normal.cross(&tangent))
+        };
+
+        let planar = |p: &Point3| {
+            (
+                (p - vertices[0]).dot(&tangent),
+                (p - vertices[0]).dot(&bitangent),
+            )
+        };
+        let (mut min, mut max) = (
+            (Float::INFINITY, Float::INFINITY),
+            (-Float::INFINITY, -Float::INFINITY),
+        );
+        for (s, t) in vertices.iter().map(planar) {
+            min = (min.0.min(s), min.1.min(t));
+            max = (max.0.max(s), max.1.max(t));
+        }
+
+        Self {
+            d: normal.dot(&vertices[0]),
+            normal,
+            tangent,
+            bitangent,
+            uv_min: min,
+            uv_size: (max.0 - min.0, max.1 - min.1),
+            vertices,
+            material,
+        }
+    }
+
+    fn contains(&self, p: Point3) -> bool {
+        let origin = self.vertices[0];
+
+        self.vertices[1..].windows(2).any(|edge| {
+            // Barycentric test against the fan triangle (origin, b, c).
+            let e1 = edge[0] - origin;
+            let e2 = edge[1] - origin;
+            let rel = p - origin;
+
+            let n = e1.cross(&e2);
+            let n2 = n.length_squared();
+            if n2 == 0.0 {
+                return false;
+            }
+
+            let beta = rel.cross(&e2).dot(&n) / n2;
+            let gamma = e1.cross(&rel).dot(&n) / n2;
+
+            beta >= 0.0 && gamma >= 0.0 && beta + gamma <= 1.0
+        })
+    }
+
+    // The t of the hit and the point hit.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Point3)> {
+        let t = plane_hit(r, self.normal, self.d, t_min, t_max)?;
+        let p = r.at(t);
+
+        if !self.contains(p) {
+            return None;
+        }
+
+        Some((t, p))
+    }
+}
+
+impl Hittable for Polygon {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, p) = self.intersect(r, t_min, t_max)?;
+
+        let planar = p - self.vertices[0];
+        let u = (planar.dot(&self.tangent) - self.uv_min.0) / self.uv_size.0;
+        let v = (planar.dot(&self.bitangent) - self.uv_min.1) / self.uv_size.1;
+
+        let (normal, front_face) = face_normal_and_is_front(r, self.normal);
+
+        Some(HitRecord {
+            p,
+            p_error: ray_point_error(r, t),
+            normal,
+            geometric_normal: self.normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(points_bounding_box(&self.vertices))
+    }
+}
+
+// The t of the hit with the plane n . p = d, if it is within range.
+#[inline]
+fn plane_hit(r: &Ray, normal: Vec3, d: Float, t_min: Float, t_max: Float) -> Option<Float> {
+    let denom = normal.dot(&r.direction);
+
+    // The ray is parallel to the plane.
+    if denom.abs() < 1e-8 {
+        return None;
+    }
+
+    let t = (d - normal.dot(&r.origin)) / denom;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some(t)
+}
+
+// Planar shapes have a flat bounding box when they are axis aligned, so pad
+// it the same way the rectangles do.
+fn points_bounding_box(points: &[Point3]) -> AABB {
+    let mut min = points[0];
+    let mut max = points[0];
+
+    for p in &points[1..] {
+        for a in 0..3 {
+            min[a] = min[a].min(p[a]);
+            max[a] = max[a].max(p[a]);
+        }
+    }
+
+    for a in 0..3 {
+        if max[a] - min[a] < PADDING {
+            min[a] -= PADDING;
+            max[a] += PADDING;
+        }
+    }
+
+    aabb(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material() -> Rc<dyn Material> {
+        Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hits_a_quad_with_uvs_along_its_edges() {
+        // Two wide and one high, facing +Z.
+        let quad = Quad::new(
+            point3(0.0, 0.0, 0.0),
+            vec3(2.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            material(),
+        );
+
+        let front = Ray::new(point3(1.5, 0.25, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = quad.hit(&front, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-5);
+        assert!((rec.u - 0.75).abs() < 1e-5 && (rec.v - 0.25).abs() < 1e-5);
+        assert_eq!(rec.normal, vec3(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+
+        let back = Ray::new(point3(1.5, 0.25, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let rec = quad.hit(&back, 0.0, Float::INFINITY).unwrap();
+        assert_eq!(rec.normal, vec3(0.0, 0.0, -1.0));
+        assert_eq!(rec.geometric_normal, vec3(0.0, 0.0, 1.0));
+        assert!(!rec.front_face);
+
+        let beside = Ray::new(point3(2.5, 0.25, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(quad.hit(&beside, 0.0, Float::INFINITY).is_none());
+        let along = Ray::new(point3(-1.0, 0.5, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(quad.hit(&along, 0.0, Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn hits_a_slanted_parallelogram() {
+        // Sheared along X, tilted to face +Z and -Y.
+        let quad = Quad::new(
+            point3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 1.0),
+            material(),
+        );
+        let n = vec3(0.0, -1.0, 1.0).unit();
+
+        // Where the shear leaves a gap, and where it moves the surface to.
+        let down = |x: Float| Ray::new(point3(x, 0.5, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(quad.hit(&down(0.25), 0.0, Float::INFINITY).is_none());
+
+        let rec = quad.hit(&down(1.25), 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-5);
+        assert!((rec.u - 0.75).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+        assert!((rec.normal - n).length() < 1e-5);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn hits_a_polygon() {
+        // A house: a unit square with a roof, wound to face +Z.
+        let polygon = Polygon::new(
+            vec![
+                point3(0.0, 0.0, 0.0),
+                point3(1.0, 0.0, 0.0),
+                point3(1.0, 1.0, 0.0),
+                point3(0.5, 1.5, 0.0),
+                point3(0.0, 1.0, 0.0),
+            ],
+            material(),
+        );
+
+        let down = |x: Float, y: Float| Ray::new(point3(x, y, 1.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = polygon.hit(&down(0.5, 1.25), 0.0, Float::INFINITY).unwrap();
+        assert_eq!(rec.normal, vec3(0.0, 0.0, 1.0));
+        assert!((rec.u - 0.5).abs() < 1e-5 && (rec.v - 1.25 / 1.5).abs() < 1e-5);
+
+        assert!(polygon
+            .hit(&down(0.1, 1.45), 0.0, Float::INFINITY)
+            .is_none());
+    }
+}
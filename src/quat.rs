@@ -0,0 +1,151 @@
+use crate::vec3::*;
+
+// A rotation quaternion w + v, where v holds the (i, j, k) components.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quat {
+    pub w: Float,
+    pub v: Vec3,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        w: 1.0,
+        v: vec3(0.0, 0.0, 0.0),
+    };
+
+    #[inline]
+    pub const fn new(w: Float, v: Vec3) -> Self {
+        Self { w, v }
+    }
+
+    // Rotation by `angle` degrees counter-clockwise about `axis`.
+    pub fn from_axis_angle(axis: Vec3, angle: Float) -> Self {
+        let half = angle.to_radians() / 2.0;
+        Self::new(half.cos(), half.sin() * axis.unit())
+    }
+
+    #[inline]
+    pub fn dot(&self, q: &Quat) -> Float {
+        self.w * q.w + self.v.dot(&q.v)
+    }
+
+    #[inline]
+    pub fn length(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    #[inline]
+    pub fn normalize(&self) -> Quat {
+        let inv = 1.0 / self.length();
+        Quat::new(self.w * inv, self.v * inv)
+    }
+
+    #[inline]
+    pub fn conjugate(&self) -> Quat {
+        Quat::new(self.w, -self.v)
+    }
+
+    // Spherical linear interpolation along the shortest arc.
+    pub fn slerp(&self, q: &Quat, t: Float) -> Quat {
+        let mut cos_theta = self.dot(q);
+        let mut end = *q;
+
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            end = Quat::new(-q.w, -q.v);
+        }
+
+        // Nearly parallel, so fall back to a normalized lerp to avoid
+        // dividing by sin(theta) ~ 0.
+        if cos_theta > 0.9995 {
+            return Quat::new(self.w + t * (end.w - self.w), self.v + t * (end.v - self.v))
+                .normalize();
+        }
+
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+
+        Quat::new(a * self.w + b * end.w, a * self.v + b * end.v)
+    }
+
+    // The rotation angle, in radians, between this and `q`.
+    pub fn angle_to(&self, q: &Quat) -> Float {
+        2.0 * self.dot(q).abs().min(1.0).acos()
+    }
+
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        // v' = v + 2w(q x v) + 2q x (q x v), for a unit quaternion.
+        let t = 2.0 * self.v.cross(&v);
+        v + self.w * t + self.v.cross(&t)
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+// Hamilton product: the rotation `rhs` followed by `self`.
+impl std::ops::Mul for Quat {
+    type Output = Quat;
+
+    #[inline]
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.w - self.v.dot(&rhs.v),
+            self.w * rhs.v + rhs.w * self.v + self.v.cross(&rhs.v),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mat4::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotates_about_axis() {
+        let q = Quat::from_axis_angle(vec3(0.0, 0.0, 2.0), 90.0);
+
+        assert_near(q.rotate(vec3(1.0, 0.0, 0.0)), vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn product_composes_rotations() {
+        let a = Quat::from_axis_angle(vec3(1.0, 0.0, 0.0), 90.0);
+        let b = Quat::from_axis_angle(vec3(0.0, 1.0, 0.0), 90.0);
+        let v = vec3(0.0, 0.0, 1.0);
+
+        assert_near((a * b).rotate(v), a.rotate(b.rotate(v)));
+    }
+
+    #[test]
+    fn slerp_interpolates_angle() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(vec3(0.0, 1.0, 0.0), 90.0);
+
+        let half = a.slerp(&b, 0.5);
+
+        assert_near(
+            half.rotate(vec3(1.0, 0.0, 0.0)),
+            Quat::from_axis_angle(vec3(0.0, 1.0, 0.0), 45.0).rotate(vec3(1.0, 0.0, 0.0)),
+        );
+        assert!((a.angle_to(&b) - Float::to_radians(90.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn matches_matrix() {
+        let q = Quat::from_axis_angle(vec3(1.0, 2.0, 3.0), 47.0);
+        let m = Mat4::from_quat(q);
+        let v = vec3(-0.5, 0.25, 2.0);
+
+        assert_near(q.rotate(v), m.transform_vector(v));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::accelerator::*;
+use crate::bvh_node::*;
+use crate::camera::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::scenes::*;
+use crate::vec3::*;
+
+use std::rc::Rc;
+
+// The closest hit of one ray, without anything needed only for shading.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    pub t: Float,
+    pub p: Point3,
+    // Facing against the ray, like HitRecord::normal.
+    pub normal: Vec3,
+    pub u: Float,
+    pub v: Float,
+    pub front_face: bool,
+    // The index of the object in the slice the query was built from.
+    pub object: u32,
+}
+
+// The object seen through a pixel.
+pub struct Pick<'a> {
+    pub object: u32,
+    pub p: Point3,
+    pub material: &'a dyn Material,
+}
+
+// Ray queries against a set of objects, for tools that want the geometry of
+// a scene without rendering it: collision and visibility tests and such.
+pub struct GeometryQuery {
+    bvh: BVHNode,
+}
+
+impl GeometryQuery {
+    pub fn new(objects: &[HittableInstance], time0: Float, time1: Float) -> Self {
+        let objects: Vec<HittableInstance> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| -> HittableInstance {
+                Rc::new(Identified::new(i as u32, object.clone()))
+            })
+            .collect();
+
+        Self {
+            bvh: BVHNode::new(&objects, time0, time1),
+        }
+    }
+
+    // The objects of `scene.world`, over the same shutter interval the
+    // renderer uses.
+    pub fn from_scene(scene: &Scene) -> Self {
+        Self::new(&scene.world, 0.0, 1.0)
+    }
+
+    pub fn closest_hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<RayHit> {
+        let rec = self.bvh.hit(r, t_min, t_max)?;
+
+        Some(RayHit {
+            t: rec.t,
+            p: rec.p,
+            normal: rec.normal,
+            u: rec.u,
+            v: rec.v,
+            front_face: rec.front_face,
+            object: rec
+                .object
+                .expect("Every object of a GeometryQuery has an id"),
+        })
+    }
+
+    // The closest hit of each ray in [t_min, t_max], in the same order.
+    pub fn closest_hits(&self, rays: &[Ray], t_min: Float, t_max: Float) -> Vec<Option<RayHit>> {
+        rays.iter()
+            .map(|r| self.closest_hit(r, t_min, t_max))
+            .collect()
+    }
+
+    // Whether anything is in the way of each ray within [t_min, t_max].  To
+    // test whether two points see each other, aim the ray from one to the
+    // other and stop just short of t = 1.
+    pub fn occluded(&self, rays: &[Ray], t_min: Float, t_max: Float) -> Vec<bool> {
+        rays.iter()
+            .map(|r| self.bvh.occluded(r, t_min, t_max))
+            .collect()
+    }
+
+    // What is seen through the middle of pixel (x, y) of a `width` by
+    // `height` image from `camera`, counting rows from the top like the
+    // rendered image.  Nothing is seen outside of the image.
+    pub fn pick(
+        &self,
+        camera: &Camera,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<Pick<'_>> {
+        if x >= width || y >= height {
+            return None;
+        }
+
+        // An image one pixel across sees along the middle of the view.
+        let s = (x as Float + 0.5) / (width - 1).max(1) as Float;
+        let t = ((height - 1 - y) as Float + 0.5) / (height - 1).max(1) as Float;
+
+        let rec = self
+            .bvh
+            .hit(&camera.center_ray(s, t), 0.0, Float::INFINITY)?;
+
+        Some(Pick {
+            object: rec
+                .object
+                .expect("Every object of a GeometryQuery has an id"),
+            p: rec.p,
+            material: rec.material,
+        })
+    }
+
+    pub fn stats(&self) -> AcceleratorStats {
+        self.bvh.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::*;
+
+    #[test]
+    fn reports_which_object_was_hit() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let objects: Vec<HittableInstance> = (0..3)
+            .map(|i| -> HittableInstance {
+                let center = point3(3.0 * i as Float, 0.0, 0.0);
+                Rc::new(Sphere::new(center, 1.0, material.clone()))
+            })
+            .collect();
+        let query = GeometryQuery::new(&objects, 0.0, 1.0);
+
+        let rays: Vec<Ray> = (0..3)
+            .map(|i| {
+                Ray::new(
+                    point3(3.0 * i as Float, 0.0, 5.0),
+                    vec3(0.0, 0.0, -1.0),
+                    0.0,
+                )
+            })
+            .chain([Ray::new(point3(0.0, 5.0, 5.0), vec3(0.0, 0.0, -1.0), 0.0)])
+            .collect();
+
+        let hits = query.closest_hits(&rays, 0.0, Float::INFINITY);
+        for (i, hit) in hits[..3].iter().enumerate() {
+            let hit = hit.unwrap();
+            assert_eq!(hit.object, i as u32);
+            assert!((hit.t - 4.0).abs() < 1e-4);
+            assert!((hit.normal - vec3(0.0, 0.0, 1.0)).length() < 1e-4);
+            assert!(hit.front_face);
+        }
+        assert!(hits[3].is_none());
+
+        // Looking along the row, the first sphere hides the others unless
+        // the ray stops short of it.
+        let along = [Ray::new(point3(-5.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0)];
+        assert_eq!(query.occluded(&along, 0.0, Float::INFINITY), [true]);
+        assert_eq!(query.occluded(&along, 0.0, 3.5), [false]);
+        assert_eq!(
+            query.closest_hits(&along, 0.0, Float::INFINITY)[0]
+                .unwrap()
+                .object,
+            0
+        );
+
+        #[cfg(feature = "stats")]
+        assert_eq!(query.stats().rays, 7);
+    }
+
+    #[test]
+    fn picks_the_object_under_a_pixel() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let red: Rc<dyn Material> = Rc::new(DiffuseLight::new(color(1.0, 0.0, 0.0)));
+        let objects: Vec<HittableInstance> = vec![
+            Rc::new(Sphere::new(point3(-1.5, 0.0, 0.0), 0.5, material)),
+            Rc::new(Sphere::new(point3(0.0, 0.0, 0.0), 0.5, red)),
+        ];
+        let query = GeometryQuery::new(&objects, 0.0, 1.0);
+
+        let camera = Camera::new(
+            point3(0.0, 0.0, 10.0),
+            point3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            20.0,
+            1.0,
+            2.0,
+            10.0,
+            0.0,
+            1.0,
+        );
+
+        // The middle of the image sees the red sphere, whatever the aperture.
+        let pick = query.pick(&camera, 50, 50, 101, 101).unwrap();
+        assert_eq!(pick.object, 1);
+        assert!((pick.p - point3(0.0, 0.0, 0.5)).length() < 0.05);
+        assert_eq!(
+            pick.material.emitted(0.0, 0.0, &pick.p),
+            color(1.0, 0.0, 0.0)
+        );
+
+        // Left of it is the other sphere, and nothing above.
+        assert_eq!(query.pick(&camera, 8, 50, 101, 101).unwrap().object, 0);
+        assert!(query.pick(&camera, 50, 5, 101, 101).is_none());
+
+        // Outside of the image, and images one pixel across.
+        assert!(query.pick(&camera, 101, 50, 101, 101).is_none());
+        assert!(query.pick(&camera, 50, 101, 101, 101).is_none());
+        assert!(query.pick(&camera, 0, 0, 0, 0).is_none());
+        assert_eq!(query.pick(&camera, 0, 0, 1, 1).unwrap().object, 1);
+        assert_eq!(query.pick(&camera, 50, 0, 101, 1).unwrap().object, 1);
+    }
+}
@@ -3,11 +3,11 @@ use crate::vec3::*;
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
-    pub time: f32,
+    pub time: Float,
 }
 
 impl Ray {
-    pub fn new(origin: Vec3, direction: Vec3, time: f32) -> Ray {
+    pub fn new(origin: Vec3, direction: Vec3, time: Float) -> Ray {
         Ray {
             origin,
             direction,
@@ -15,7 +15,7 @@ impl Ray {
         }
     }
 
-    pub fn at(&self, t: f32) -> Vec3 {
+    pub fn at(&self, t: Float) -> Vec3 {
         self.origin + t * self.direction
     }
 }
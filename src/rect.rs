@@ -5,19 +5,26 @@ use crate::ray::*;
 use crate::vec3::*;
 use std::rc::Rc;
 
-const PADDING: f32 = 0.0001;
+const PADDING: Float = 0.0001;
 
 pub struct XY {
     material: Rc<dyn Material>,
-    x0: f32,
-    x1: f32,
-    y0: f32,
-    y1: f32,
-    k: f32,
+    x0: Float,
+    x1: Float,
+    y0: Float,
+    y1: Float,
+    k: Float,
 }
 
 impl XY {
-    pub fn new(x0: f32, x1: f32, y0: f32, y1: f32, k: f32, material: Rc<dyn Material>) -> Self {
+    pub fn new(
+        x0: Float,
+        x1: Float,
+        y0: Float,
+        y1: Float,
+        k: Float,
+        material: Rc<dyn Material>,
+    ) -> Self {
         Self {
             material,
             x0,
@@ -27,10 +34,9 @@ impl XY {
             k,
         }
     }
-}
 
-impl Hittable for XY {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    // The t of the hit and where it is on the rectangle.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
         let t = (self.k - r.origin.z) / r.direction.z;
 
         if t < t_min || t > t_max {
@@ -44,6 +50,14 @@ impl Hittable for XY {
             return None;
         }
 
+        Some((t, x, y))
+    }
+}
+
+impl Hittable for XY {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, x, y) = self.intersect(r, t_min, t_max)?;
+
         let u = (x - self.x0) / (self.x1 - self.x0);
         let v = (y - self.y0) / (self.y1 - self.y0);
 
@@ -53,16 +67,25 @@ impl Hittable for XY {
 
         Some(HitRecord {
             p: r.at(t),
+            p_error: ray_point_error(r, t),
             t,
             normal,
-            material: self.material.clone(),
+            geometric_normal: outward_normal,
+            material: &*self.material,
             u,
             v,
             front_face,
+            face: 0,
+            mesh: None,
+            object: None,
         })
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
         // The bounding box must have non-zero width in each dimension, so pad the Z
         // dimension a small amount.
         Some(aabb(
@@ -74,15 +97,22 @@ impl Hittable for XY {
 
 pub struct XZ {
     material: Rc<dyn Material>,
-    x0: f32,
-    x1: f32,
-    z0: f32,
-    z1: f32,
-    k: f32,
+    x0: Float,
+    x1: Float,
+    z0: Float,
+    z1: Float,
+    k: Float,
 }
 
 impl XZ {
-    pub fn new(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, material: Rc<dyn Material>) -> Self {
+    pub fn new(
+        x0: Float,
+        x1: Float,
+        z0: Float,
+        z1: Float,
+        k: Float,
+        material: Rc<dyn Material>,
+    ) -> Self {
         Self {
             material,
             x0,
@@ -92,10 +122,9 @@ impl XZ {
             k,
         }
     }
-}
 
-impl Hittable for XZ {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    // The t of the hit and where it is on the rectangle.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
         let t = (self.k - r.origin.y) / r.direction.y;
 
         if t < t_min || t > t_max {
@@ -109,6 +138,14 @@ impl Hittable for XZ {
             return None;
         }
 
+        Some((t, x, z))
+    }
+}
+
+impl Hittable for XZ {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, x, z) = self.intersect(r, t_min, t_max)?;
+
         let u = (x - self.x0) / (self.x1 - self.x0);
         let v = (z - self.z0) / (self.z1 - self.z0);
 
@@ -118,16 +155,25 @@ impl Hittable for XZ {
 
         Some(HitRecord {
             p: r.at(t),
+            p_error: ray_point_error(r, t),
             t,
             normal,
-            material: self.material.clone(),
+            geometric_normal: outward_normal,
+            material: &*self.material,
             u,
             v,
             front_face,
+            face: 0,
+            mesh: None,
+            object: None,
         })
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
         // The bounding box must have non-zero width in each dimension, so pad the Y
         // dimension a small amount.
         Some(aabb(
@@ -139,15 +185,22 @@ impl Hittable for XZ {
 
 pub struct YZ {
     material: Rc<dyn Material>,
-    y0: f32,
-    y1: f32,
-    z0: f32,
-    z1: f32,
-    k: f32,
+    y0: Float,
+    y1: Float,
+    z0: Float,
+    z1: Float,
+    k: Float,
 }
 
 impl YZ {
-    pub fn new(y0: f32, y1: f32, z0: f32, z1: f32, k: f32, material: Rc<dyn Material>) -> Self {
+    pub fn new(
+        y0: Float,
+        y1: Float,
+        z0: Float,
+        z1: Float,
+        k: Float,
+        material: Rc<dyn Material>,
+    ) -> Self {
         Self {
             material,
             y0,
@@ -157,10 +210,9 @@ impl YZ {
             k,
         }
     }
-}
 
-impl Hittable for YZ {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    // The t of the hit and where it is on the rectangle.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<(Float, Float, Float)> {
         let t = (self.k - r.origin.x) / r.direction.x;
 
         if t < t_min || t > t_max {
@@ -174,6 +226,14 @@ impl Hittable for YZ {
             return None;
         }
 
+        Some((t, y, z))
+    }
+}
+
+impl Hittable for YZ {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, y, z) = self.intersect(r, t_min, t_max)?;
+
         let u = (y - self.y0) / (self.y1 - self.y0);
         let v = (z - self.z0) / (self.z1 - self.z0);
 
@@ -182,16 +242,25 @@ impl Hittable for YZ {
 
         Some(HitRecord {
             p: r.at(t),
+            p_error: ray_point_error(r, t),
             t,
             normal,
-            material: self.material.clone(),
+            geometric_normal: outward_normal,
+            material: &*self.material,
             u,
             v,
             front_face,
+            face: 0,
+            mesh: None,
+            object: None,
         })
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
         // The bounding box must have non-zero width in each dimension, so pad the X
         // dimension a small amount.
         Some(aabb(
@@ -0,0 +1,288 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::mat4::*;
+use crate::ray::*;
+use crate::texture::*;
+use crate::vec3::*;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// How many candidates to try for each placement asked for, before giving up
+// on a region that is full.
+const ATTEMPTS_PER_PLACEMENT: usize = 30;
+
+// Where one copy goes, turned `rotation` degrees about +Y and scaled by
+// `scale`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub position: Point3,
+    // The normal of the surface under the copy, facing up, or +Y when it was
+    // placed in a region.
+    pub normal: Vec3,
+    pub rotation: Float,
+    pub scale: Float,
+}
+
+impl Placement {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.position)
+            * Mat4::rotation_y(self.rotation)
+            * Mat4::scale(vec3(self.scale, self.scale, self.scale))
+    }
+
+    // A copy of `prototype` placed here.
+    pub fn instance(&self, prototype: &HittableInstance) -> HittableInstance {
+        Rc::new(Transform::new(prototype.clone(), self.matrix()))
+    }
+}
+
+// Random placements for many copies of something: trees on terrain, rocks,
+// fields of spheres.  The same seed and settings always give the same
+// placements.
+pub struct Scatter {
+    seed: u64,
+    spacing: Float,
+    scale: (Float, Float),
+    rotation: (Float, Float),
+    density: Option<Rc<dyn Texture>>,
+    avoid: Vec<(Point3, Float)>,
+}
+
+impl Scatter {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            spacing: 0.0,
+            scale: (1.0, 1.0),
+            rotation: (0.0, 0.0),
+            density: None,
+            avoid: Vec::new(),
+        }
+    }
+
+    // Keep placements at least `spacing` apart, for an even but random
+    // (Poisson disk) spread.
+    pub fn with_spacing(self, spacing: Float) -> Self {
+        Self { spacing, ..self }
+    }
+
+    pub fn with_scale(self, min: Float, max: Float) -> Self {
+        Self {
+            scale: (min, max),
+            ..self
+        }
+    }
+
+    // Turn each copy about +Y by between `min` and `max` degrees.
+    pub fn with_rotation(self, min: Float, max: Float) -> Self {
+        Self {
+            rotation: (min, max),
+            ..self
+        }
+    }
+
+    // Keep each placement with a probability of the brightness of `density`
+    // there.  In a region, u and v run from 0 to 1 across X and Z; on a
+    // surface they are the uvs of the surface.
+    pub fn with_density(self, density: Rc<dyn Texture>) -> Self {
+        Self {
+            density: Some(density),
+            ..self
+        }
+    }
+
+    // Keep placements further than `radius` from `center`.
+    pub fn avoiding(mut self, center: Point3, radius: Float) -> Self {
+        self.avoid.push((center, radius));
+        self
+    }
+
+    // Up to `count` placements anywhere in `region`.
+    pub fn in_region(&self, region: &AABB, count: usize) -> Vec<Placement> {
+        let size = region.max - region.min;
+
+        self.place(count, |rng| {
+            let (u, v) = (rng.gen::<Float>(), rng.gen::<Float>());
+            let p = region.min + vec3(u * size.x, rng.gen::<Float>() * size.y, v * size.z);
+
+            Some((p, vec3(0.0, 1.0, 0.0), u, v))
+        })
+    }
+
+    // Up to `count` placements on `surface`, where rays dropped straight down
+    // through the top of `region` meet it within the region.
+    pub fn on_surface(
+        &self,
+        surface: &dyn Hittable,
+        region: &AABB,
+        count: usize,
+    ) -> Vec<Placement> {
+        let size = region.max - region.min;
+
+        self.place(count, |rng| {
+            let x = region.min.x + rng.gen::<Float>() * size.x;
+            let z = region.min.z + rng.gen::<Float>() * size.z;
+            let r = Ray::new(point3(x, region.max.y, z), vec3(0.0, -1.0, 0.0), 0.0);
+
+            let rec = surface.hit(&r, 0.0, size.y)?;
+
+            Some((rec.p, rec.normal, rec.u, rec.v))
+        })
+    }
+
+    // Draw candidates from `sample`, which gives a point, its normal and its
+    // uvs, and keep those that pass the density, avoidance and spacing tests.
+    fn place<F>(&self, count: usize, mut sample: F) -> Vec<Placement>
+    where
+        F: FnMut(&mut SmallRng) -> Option<(Point3, Vec3, Float, Float)>,
+    {
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let mut placements: Vec<Placement> = Vec::with_capacity(count);
+
+        // The placements in each cell of a grid as fine as the spacing, so
+        // only the neighboring cells need checking.
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let cell_of = |p: Point3| [p.x, p.y, p.z].map(|x| (x / self.spacing).floor() as i64);
+
+        for _ in 0..count * ATTEMPTS_PER_PLACEMENT {
+            if placements.len() == count {
+                break;
+            }
+
+            // Draw everything up front, so that rejecting a candidate does
+            // not change the random numbers of the next one.
+            let candidate = sample(&mut rng);
+            let keep = rng.gen::<Float>();
+            let rotation = lerp(self.rotation, rng.gen());
+            let scale = lerp(self.scale, rng.gen());
+
+            let (position, normal, u, v) = match candidate {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+
+            if self
+                .avoid
+                .iter()
+                .any(|&(center, radius)| (position - center).length() <= radius)
+            {
+                continue;
+            }
+
+            if let Some(density) = &self.density {
+                let c = density.value(u, v, &position);
+                if keep >= (c.x + c.y + c.z) / 3.0 {
+                    continue;
+                }
+            }
+
+            if self.spacing > 0.0 {
+                let [i, j, k] = cell_of(position);
+                let too_close = (-1..=1)
+                    .flat_map(|di| {
+                        (-1..=1).flat_map(move |dj| (-1..=1).map(move |dk| [di, dj, dk]))
+                    })
+                    .filter_map(|[di, dj, dk]| cells.get(&[i + di, j + dj, k + dk]))
+                    .flatten()
+                    .any(|&other| (placements[other].position - position).length() < self.spacing);
+
+                if too_close {
+                    continue;
+                }
+                cells.entry([i, j, k]).or_default().push(placements.len());
+            }
+
+            placements.push(Placement {
+                position,
+                normal,
+                rotation,
+                scale,
+            });
+        }
+
+        placements
+    }
+}
+
+#[inline]
+fn lerp((min, max): (Float, Float), t: Float) -> Float {
+    min + t * (max - min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::*;
+    use crate::quad::*;
+
+    // White where u > 0.5, black elsewhere.
+    struct RightHalf;
+
+    impl Texture for RightHalf {
+        fn value(&self, u: Float, _v: Float, _p: &Point3) -> Color {
+            if u > 0.5 {
+                color(1.0, 1.0, 1.0)
+            } else {
+                color(0.0, 0.0, 0.0)
+            }
+        }
+    }
+
+    #[test]
+    fn spreads_placements_evenly_and_repeatably() {
+        let region = aabb(point3(-10.0, 0.0, -10.0), point3(10.0, 0.0, 10.0));
+        let scatter = Scatter::new(42)
+            .with_spacing(1.0)
+            .with_scale(0.5, 2.0)
+            .with_rotation(0.0, 360.0)
+            .avoiding(point3(0.0, 0.0, 0.0), 3.0);
+
+        let placements = scatter.in_region(&region, 100);
+        assert_eq!(placements.len(), 100);
+        assert_eq!(placements, scatter.in_region(&region, 100));
+        assert_ne!(placements, Scatter::new(43).in_region(&region, 100));
+
+        for (i, a) in placements.iter().enumerate() {
+            assert!(a.position.length() > 3.0);
+            assert!((0.5..=2.0).contains(&a.scale));
+            assert!((0.0..=360.0).contains(&a.rotation));
+
+            for b in &placements[i + 1..] {
+                assert!((a.position - b.position).length() >= 1.0);
+            }
+        }
+
+        // Only the white half of the density texture gets any.
+        let white_half = Scatter::new(42)
+            .with_density(Rc::new(RightHalf))
+            .in_region(&region, 50);
+        assert_eq!(white_half.len(), 50);
+        assert!(white_half.iter().all(|p| p.position.x > 0.0));
+    }
+
+    #[test]
+    fn places_on_a_surface() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        // A slope rising 1 in y for every 1 along +Z, level along x.
+        let ramp = Quad::new(
+            point3(0.0, 0.0, 0.0),
+            vec3(10.0, 0.0, 0.0),
+            vec3(0.0, 10.0, 10.0),
+            material,
+        );
+        let region = aabb(point3(-5.0, -5.0, -5.0), point3(15.0, 15.0, 15.0));
+
+        let placements = Scatter::new(1).on_surface(&ramp, &region, 20);
+        assert_eq!(placements.len(), 20);
+
+        let up = vec3(0.0, 1.0, -1.0).unit();
+        for p in placements {
+            assert!((p.position.y - p.position.z).abs() < 1e-3);
+            assert!((p.normal - up).length() < 1e-4);
+        }
+    }
+}
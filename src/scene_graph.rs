@@ -0,0 +1,169 @@
+use crate::hittable::*;
+use crate::mat4::*;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A named node of a scene graph.  Its transform is relative to its parent,
+// and applies to its own geometry and to all its children.
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Mat4,
+    pub geometry: Option<HittableInstance>,
+    pub children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            transform: Mat4::IDENTITY,
+            geometry: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_transform(self, transform: Mat4) -> Self {
+        Self { transform, ..self }
+    }
+
+    pub fn with_geometry(self, geometry: HittableInstance) -> Self {
+        Self {
+            geometry: Some(geometry),
+            ..self
+        }
+    }
+
+    pub fn with_child(mut self, child: SceneNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn add_child(&mut self, child: SceneNode) {
+        self.children.push(child);
+    }
+
+    // The descendant at `path`, a list of child names separated by '/'.  An
+    // empty path is this node.
+    pub fn find(&self, path: &str) -> Option<&SceneNode> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| {
+                node.children.iter().find(|child| child.name == name)
+            })
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut SceneNode> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| {
+                node.children.iter_mut().find(|child| child.name == name)
+            })
+    }
+
+    // The transform from the local space of the node at `path` to the space
+    // of this node.
+    pub fn transform_to(&self, path: &str) -> Option<Mat4> {
+        let mut node = self;
+        let mut transform = self.transform;
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            node = node.children.iter().find(|child| child.name == name)?;
+            transform = transform * node.transform;
+        }
+
+        Some(transform)
+    }
+
+    // The geometry of the whole graph as world objects, ready to build an
+    // accelerator over, with the path of the node each object came from as
+    // its name.  The paths start with the name of this node.
+    pub fn flatten(&self) -> (Vec<HittableInstance>, HashMap<u32, String>) {
+        let mut world = Vec::new();
+        let mut names = HashMap::new();
+
+        self.flatten_into(&Mat4::IDENTITY, "", &mut world, &mut names);
+
+        (world, names)
+    }
+
+    fn flatten_into(
+        &self,
+        parent: &Mat4,
+        parent_path: &str,
+        world: &mut Vec<HittableInstance>,
+        names: &mut HashMap<u32, String>,
+    ) {
+        let transform = *parent * self.transform;
+        let path = if parent_path.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}/{}", parent_path, self.name)
+        };
+
+        if let Some(geometry) = &self.geometry {
+            names.insert(world.len() as u32, path.clone());
+
+            if transform == Mat4::IDENTITY {
+                world.push(geometry.clone());
+            } else {
+                world.push(Rc::new(Transform::new(geometry.clone(), transform)));
+            }
+        }
+
+        for child in &self.children {
+            child.flatten_into(&transform, &path, world, names);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::*;
+    use crate::ray::*;
+    use crate::sphere::*;
+    use crate::vec3::*;
+
+    #[test]
+    fn flattens_nested_transforms() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let ball = || -> HittableInstance {
+            Rc::new(Sphere::new(point3(0.0, 0.0, 0.0), 1.0, material.clone()))
+        };
+
+        let mut root = SceneNode::new("root")
+            .with_child(
+                SceneNode::new("group")
+                    .with_transform(Mat4::translation(vec3(10.0, 0.0, 0.0)))
+                    .with_child(
+                        SceneNode::new("ball")
+                            .with_transform(Mat4::scale(vec3(2.0, 2.0, 2.0)))
+                            .with_geometry(ball()),
+                    ),
+            )
+            .with_child(SceneNode::new("origin").with_geometry(ball()));
+
+        assert_eq!(root.find("group/ball").unwrap().name, "ball");
+        assert!(root.find("ball").is_none());
+        let to_ball = root.transform_to("group/ball").unwrap();
+        assert_eq!(
+            to_ball.transform_point(point3(1.0, 0.0, 0.0)),
+            point3(12.0, 0.0, 0.0)
+        );
+
+        // Moving a group moves everything in it.
+        root.find_mut("group").unwrap().transform = Mat4::translation(vec3(0.0, 10.0, 0.0));
+
+        let (world, names) = root.flatten();
+        assert_eq!(world.len(), 2);
+        assert_eq!(names[&0], "root/group/ball");
+        assert_eq!(names[&1], "root/origin");
+
+        // The ball is now at (0, 10, 0) with radius 2.
+        let r = Ray::new(point3(0.0, 10.0, 10.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = world[0].hit(&r, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 8.0).abs() < 1e-4);
+        assert!(world[1].hit(&r, 0.0, Float::INFINITY).is_none());
+    }
+}
@@ -1,20 +1,35 @@
 use crate::*;
 
+use std::collections::HashMap;
 use std::rc::Rc;
 
-const APERTURE: f32 = 0.0;
+const APERTURE: Float = 0.0;
 const SAMPLES_PER_PIXEL: i32 = 100;
 
 type World = Vec<HittableInstance>;
 
 pub struct Scene {
     pub world: World,
+    // Names of some of the objects in `world`, by index.  An object's index
+    // is its id in picking and in GeometryQuery hits.
+    pub names: HashMap<u32, String>,
     pub background: Vec3,
     pub lookfrom: Vec3,
     pub lookat: Vec3,
-    pub vfov: f32,
-    pub aperture: f32,
+    // Which way is up in the image.
+    pub vup: Vec3,
+    pub vfov: Float,
+    // The width of the image over its height.
+    pub aspect_ratio: Float,
+    pub aperture: Float,
     pub samples_per_pixel: i32,
+    pub accelerator: AcceleratorKind,
+}
+
+impl Scene {
+    pub fn object_name(&self, id: u32) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
 }
 
 // Static test scene used for profiling.
@@ -43,12 +58,16 @@ pub fn test() -> Scene {
 
     Scene {
         world,
+        names: HashMap::new(),
         background: color(0.7, 0.8, 1.0),
         lookfrom: point3(13.0, 2.0, 3.0),
         lookat: point3(0.0, 0.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 20.0,
+        aspect_ratio: 1.0,
         aperture: 0.1,
         samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
     }
 }
 
@@ -75,12 +94,16 @@ pub fn two_spheres() -> Scene {
 
     Scene {
         world,
+        names: HashMap::new(),
         background: color(0.7, 0.8, 1.0),
         lookfrom: point3(13.0, 2.0, 3.0),
         lookat: point3(0.0, 0.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 20.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
         samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
     }
 }
 
@@ -104,12 +127,16 @@ pub fn two_perlin_spheres() -> Scene {
 
     Scene {
         world,
+        names: HashMap::new(),
         background: color(0.7, 0.8, 1.0),
         lookfrom: point3(13.0, 2.0, 3.0),
         lookat: point3(0.0, 0.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 20.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
         samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
     }
 }
 
@@ -129,38 +156,39 @@ pub fn random() -> Scene {
         ground_material,
     )));
 
-    let shift = point3(4.0, 0.2, 0.0);
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = super::random();
-            let center = point3(
-                a as f32 + 0.9 * super::random(),
-                0.2,
-                b as f32 + 0.9 * super::random(),
-            );
-
-            if (center - shift).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Color::random() * Color::random();
-                    let material = Rc::new(Lambertian::new(albedo));
-                    let center2 = center + vec3(0.0, random_range(0.0, 0.5), 0.0);
-                    world.push(Rc::new(MovingSphere::new(
-                        center, center2, 0.0, 1.0, 0.2, material,
-                    )));
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = random_range(0.0, 0.5);
-                    let material = Rc::new(Metal::new(albedo, fuzz));
-                    world.push(Rc::new(Sphere::new(center, 0.2, material)));
-                } else {
-                    // glass
-                    let material = Rc::new(Dielectric::new(1.5));
-                    world.push(Rc::new(Sphere::new(center, 0.2, material)));
-                }
-            }
+    // Small spheres over the ground, clear of the three big ones.
+    let placements = Scatter::new(2020)
+        .with_spacing(0.6)
+        .avoiding(point3(0.0, 1.0, 0.0), 1.25)
+        .avoiding(point3(-4.0, 1.0, 0.0), 1.25)
+        .avoiding(point3(4.0, 1.0, 0.0), 1.25)
+        .in_region(
+            &aabb(point3(-11.0, 0.2, -11.0), point3(11.0, 0.2, 11.0)),
+            484,
+        );
+
+    for placement in placements {
+        let choose_mat = super::random();
+        let center = placement.position;
+
+        if choose_mat < 0.8 {
+            // diffuse
+            let albedo = Color::random() * Color::random();
+            let material = Rc::new(Lambertian::new(albedo));
+            let center2 = center + vec3(0.0, random_range(0.0, 0.5), 0.0);
+            world.push(Rc::new(MovingSphere::new(
+                center, center2, 0.0, 1.0, 0.2, material,
+            )));
+        } else if choose_mat < 0.95 {
+            // metal
+            let albedo = Color::random_range(0.5, 1.0);
+            let fuzz = random_range(0.0, 0.5);
+            let material = Rc::new(Metal::new(albedo, fuzz));
+            world.push(Rc::new(Sphere::new(center, 0.2, material)));
+        } else {
+            // glass
+            let material = Rc::new(Dielectric::new(1.5));
+            world.push(Rc::new(Sphere::new(center, 0.2, material)));
         }
     }
 
@@ -175,12 +203,16 @@ pub fn random() -> Scene {
 
     Scene {
         world,
+        names: HashMap::new(),
         background: color(0.7, 0.8, 1.0),
         lookfrom: point3(13.0, 2.0, 3.0),
         lookat: point3(0.0, 0.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 20.0,
+        aspect_ratio: 1.0,
         aperture: 0.1,
         samples_per_pixel: 500,
+        accelerator: AcceleratorKind::Bvh,
     }
 }
 
@@ -194,12 +226,16 @@ pub fn earth() -> Scene {
 
     Scene {
         world,
+        names: HashMap::new(),
         background: color(0.7, 0.8, 1.0),
         lookfrom: point3(13.0, 2.0, 3.0),
         lookat: point3(0.0, 0.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 20.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
         samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
     }
 }
 
@@ -224,78 +260,309 @@ pub fn simple_light() -> Scene {
 
     Scene {
         world,
+        names: HashMap::new(),
         samples_per_pixel: 400,
+        accelerator: AcceleratorKind::Bvh,
         background: color(0.0, 0.0, 0.0),
         lookfrom: point3(26.0, 3.0, 6.0),
         lookat: point3(0.0, 2.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 20.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
     }
 }
 
-pub fn cornell_box() -> Scene {
+#[allow(dead_code)]
+pub fn primitives() -> Scene {
     let mut world: World = vec![];
 
+    let checker = Rc::new(CheckerTexture::from_color(
+        color(0.2, 0.3, 0.1),
+        color(0.9, 0.9, 0.9),
+    ));
+    world.push(Rc::new(Sphere::new(
+        point3(0.0, -1000.0, 0.0),
+        1000.0,
+        Rc::new(Lambertian::new_from_texture(checker)),
+    )));
+
     let red = Rc::new(Lambertian::new(color(0.65, 0.05, 0.05)));
-    let white = Rc::new(Lambertian::new(color(0.73, 0.73, 0.73)));
-    let green = Rc::new(Lambertian::new(color(0.12, 0.45, 0.15)));
-    let light = Rc::new(DiffuseLight::new(color(15.0, 15.0, 15.0)));
+    let gold = Rc::new(Metal::new(color(0.8, 0.6, 0.2), 0.1));
+    let glass = Rc::new(Dielectric::new(1.5));
+    let blue = Rc::new(Lambertian::new(color(0.1, 0.2, 0.5)));
 
-    world.push(Rc::new(rect::YZ::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
-    world.push(Rc::new(rect::YZ::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
-    world.push(Rc::new(rect::XZ::new(
-        213.0, 343.0, 227.0, 332.0, 554.0, light,
+    world.push(Rc::new(
+        Cylinder::new(point3(-4.0, 0.0, 0.0), 0.8, 2.0, red).with_sweep(270.0),
+    ));
+    world.push(Rc::new(Cone::new(point3(-2.0, 0.0, 0.0), 0.8, 2.0, gold)));
+    world.push(Rc::new(Transform::new(
+        Rc::new(Torus::new(point3(0.0, 0.0, 0.0), 0.7, 0.25, blue.clone())),
+        Mat4::translation(vec3(0.0, 1.0, 0.0)) * Mat4::rotation_x(60.0),
     )));
-    world.push(Rc::new(rect::XZ::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        0.0,
-        white.clone(),
+    world.push(Rc::new(Capsule::new(
+        point3(2.0, 0.5, -0.5),
+        point3(2.0, 1.5, 0.5),
+        0.5,
+        glass,
     )));
-    world.push(Rc::new(rect::XZ::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
+    world.push(Rc::new(Disk::annulus(
+        point3(4.0, 1.0, 0.0),
+        vec3(1.0, 0.0, 1.0),
+        0.4,
+        0.9,
+        blue,
     )));
-    world.push(Rc::new(rect::XY::new(
-        0.0,
-        555.0,
-        0.0,
-        555.0,
-        555.0,
-        white.clone(),
+
+    let white = Rc::new(Lambertian::new(color(0.73, 0.73, 0.73)));
+    world.push(Rc::new(Cube::oriented(
+        point3(3.0, 0.0, -4.0),
+        vec3(1.5, 0.0, -0.8),
+        vec3(0.3, 1.5, 0.0),
+        vec3(0.5, 0.0, 1.0),
+        white,
     )));
 
-    let cube1 = Cube::new(
-        point3(0.0, 0.0, 0.0),
-        point3(165.0, 330.0, 165.0),
-        white.clone(),
-    );
-    let cube1_rotated = RotateY::new(Rc::new(cube1), 15.0);
-    let cube1_translated = Translate::new(Rc::new(cube1_rotated), vec3(265.0, 0.0, 295.0));
-    world.push(Rc::new(cube1_translated));
+    let hexagon = (0..6)
+        .map(|i| {
+            let angle = (i as Float * 60.0).to_radians();
+            point3(-2.0 + angle.cos(), 2.5 + angle.sin(), -4.0)
+        })
+        .collect();
+    world.push(Rc::new(Polygon::new(
+        hexagon,
+        Rc::new(DiffuseLight::new(color(4.0, 4.0, 4.0))),
+    )));
 
-    let cube2 = Cube::new(
-        point3(0.0, 0.0, 0.0),
-        point3(165.0, 165.0, 165.0),
-        white.clone(),
+    Scene {
+        world,
+        names: HashMap::new(),
+        background: color(0.7, 0.8, 1.0),
+        lookfrom: point3(0.0, 4.0, 14.0),
+        lookat: point3(0.0, 0.8, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
+        vfov: 40.0,
+        aspect_ratio: 1.0,
+        aperture: APERTURE,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
+    }
+}
+
+#[allow(dead_code)]
+pub fn csg() -> Scene {
+    let mut world: World = vec![];
+
+    let checker = Rc::new(CheckerTexture::from_color(
+        color(0.2, 0.3, 0.1),
+        color(0.9, 0.9, 0.9),
+    ));
+    world.push(Rc::new(Sphere::new(
+        point3(0.0, -1000.0, 0.0),
+        1000.0,
+        Rc::new(Lambertian::new_from_texture(checker)),
+    )));
+
+    let red = Rc::new(Lambertian::new(color(0.65, 0.05, 0.05)));
+    let gold = Rc::new(Metal::new(color(0.8, 0.6, 0.2), 0.1));
+    let glass = Rc::new(Dielectric::new(1.5));
+
+    // A cube with a sphere scooped out of its top corner.
+    let cube = Rc::new(Cube::new(
+        point3(-4.0, 0.0, -1.0),
+        point3(-2.0, 2.0, 1.0),
+        red,
+    ));
+    let scoop = Rc::new(Sphere::new(point3(-2.0, 2.0, 1.0), 1.2, gold));
+    world.push(Rc::new(Csg::difference(cube, scoop)));
+
+    // A biconvex lens, where two large spheres overlap.
+    let back = Rc::new(Sphere::new(point3(0.0, 1.6, -2.6), 3.0, glass.clone()));
+    let front = Rc::new(Sphere::new(point3(0.0, 1.6, 2.6), 3.0, glass));
+    world.push(Rc::new(Csg::intersection(back, front)));
+
+    // Smoke filling a hollow shell, which is not convex.
+    let white = Rc::new(Lambertian::new(color(0.73, 0.73, 0.73)));
+    let outer = Rc::new(Sphere::new(point3(3.0, 1.0, 0.0), 1.0, white.clone()));
+    let inner = Rc::new(Sphere::new(point3(3.0, 1.0, 0.0), 0.6, white));
+    world.push(Rc::new(ConstantMedium::with_color(
+        Rc::new(Csg::difference(outer, inner)),
+        4.0,
+        color(0.1, 0.2, 0.5),
+    )));
+
+    Scene {
+        world,
+        names: HashMap::new(),
+        background: color(0.7, 0.8, 1.0),
+        lookfrom: point3(0.0, 4.0, 14.0),
+        lookat: point3(0.0, 0.8, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
+        vfov: 35.0,
+        aspect_ratio: 1.0,
+        aperture: APERTURE,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
+    }
+}
+
+// Block terrain: Perlin hills of grass over dirt over stone, with a glass
+// block sitting in the middle.
+#[allow(dead_code)]
+pub fn voxels() -> Scene {
+    let mut world: World = vec![];
+
+    let materials: Vec<Rc<dyn Material>> = vec![
+        Rc::new(Lambertian::new(color(0.3, 0.6, 0.2))),
+        Rc::new(Lambertian::new(color(0.45, 0.3, 0.15))),
+        Rc::new(Lambertian::new(color(0.5, 0.5, 0.5))),
+        Rc::new(Dielectric::new(1.5)),
+    ];
+    let (grass, dirt, stone, glass) = (1, 2, 3, 4);
+
+    const SIZE: usize = 48;
+    const HEIGHT: usize = 24;
+    let mut grid = VoxelGrid::dense(
+        point3(-(SIZE as Float) / 2.0, 0.0, -(SIZE as Float) / 2.0),
+        1.0,
+        [SIZE, HEIGHT, SIZE],
+        materials,
     );
-    let cube2_rotated = RotateY::new(Rc::new(cube2), -18.0);
-    let cube2_translated = Translate::new(Rc::new(cube2_rotated), vec3(130.0, 0.0, 65.0));
-    world.push(Rc::new(cube2_translated));
+
+    let noise = Perlin::new();
+    for x in 0..SIZE {
+        for z in 0..SIZE {
+            let p = point3(x as Float, 0.0, z as Float) * 0.05;
+            let top = 2 + (noise.turb(&p, 5) * 12.0) as usize;
+
+            for y in 0..top.min(HEIGHT) {
+                let block = match top - y {
+                    1 => grass,
+                    2..=3 => dirt,
+                    _ => stone,
+                };
+                grid.set([x, y, z], block);
+            }
+        }
+    }
+
+    // Resting on the highest ground under it.
+    let center = SIZE / 2;
+    let ground = (center - 2..center + 2)
+        .flat_map(|x| (center - 2..center + 2).map(move |z| [x, z]))
+        .map(|[x, z]| {
+            (0..HEIGHT)
+                .rev()
+                .find(|&y| grid.get([x, y, z]) != EMPTY)
+                .unwrap_or(0)
+        })
+        .max()
+        .unwrap_or(0);
+
+    for y in ground + 1..(ground + 5).min(HEIGHT) {
+        for x in center - 2..center + 2 {
+            for z in center - 2..center + 2 {
+                grid.set([x, y, z], glass);
+            }
+        }
+    }
+
+    world.push(Rc::new(grid));
 
     Scene {
         world,
+        names: HashMap::new(),
+        background: color(0.7, 0.8, 1.0),
+        lookfrom: point3(40.0, 35.0, 50.0),
+        lookat: point3(0.0, 8.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
+        vfov: 40.0,
+        aspect_ratio: 1.0,
+        aperture: APERTURE,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        accelerator: AcceleratorKind::Bvh,
+    }
+}
+
+pub fn cornell_box() -> Scene {
+    let red = Rc::new(Lambertian::new(color(0.65, 0.05, 0.05)));
+    let white = Rc::new(Lambertian::new(color(0.73, 0.73, 0.73)));
+    let green = Rc::new(Lambertian::new(color(0.12, 0.45, 0.15)));
+    let light = Rc::new(DiffuseLight::new(color(15.0, 15.0, 15.0)));
+
+    let walls = SceneNode::new("walls")
+        .with_child(
+            SceneNode::new("green")
+                .with_geometry(Rc::new(rect::YZ::new(0.0, 555.0, 0.0, 555.0, 555.0, green))),
+        )
+        .with_child(
+            SceneNode::new("red")
+                .with_geometry(Rc::new(rect::YZ::new(0.0, 555.0, 0.0, 555.0, 0.0, red))),
+        )
+        .with_child(SceneNode::new("floor").with_geometry(Rc::new(rect::XZ::new(
+            0.0,
+            555.0,
+            0.0,
+            555.0,
+            0.0,
+            white.clone(),
+        ))))
+        .with_child(
+            SceneNode::new("ceiling").with_geometry(Rc::new(rect::XZ::new(
+                0.0,
+                555.0,
+                0.0,
+                555.0,
+                555.0,
+                white.clone(),
+            ))),
+        )
+        .with_child(SceneNode::new("back").with_geometry(Rc::new(rect::XY::new(
+            0.0,
+            555.0,
+            0.0,
+            555.0,
+            555.0,
+            white.clone(),
+        ))));
+
+    let tall_box = SceneNode::new("tall box")
+        .with_transform(Mat4::translation(vec3(265.0, 0.0, 295.0)) * Mat4::rotation_y(15.0))
+        .with_geometry(Rc::new(Cube::new(
+            point3(0.0, 0.0, 0.0),
+            point3(165.0, 330.0, 165.0),
+            white.clone(),
+        )));
+
+    let short_box = SceneNode::new("short box")
+        .with_transform(Mat4::translation(vec3(130.0, 0.0, 65.0)) * Mat4::rotation_y(-18.0))
+        .with_geometry(Rc::new(Cube::new(
+            point3(0.0, 0.0, 0.0),
+            point3(165.0, 165.0, 165.0),
+            white,
+        )));
+
+    let room = SceneNode::new("cornell box")
+        .with_child(walls)
+        .with_child(SceneNode::new("light").with_geometry(Rc::new(rect::XZ::new(
+            213.0, 343.0, 227.0, 332.0, 554.0, light,
+        ))))
+        .with_child(tall_box)
+        .with_child(short_box);
+
+    let (world, names) = room.flatten();
+
+    Scene {
+        world,
+        names,
         samples_per_pixel: 200,
+        accelerator: AcceleratorKind::Bvh,
         background: color(0.0, 0.0, 0.0),
         lookfrom: point3(278.0, 278.0, -800.0),
         lookat: point3(278.0, 278.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 40.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
     }
 }
@@ -343,10 +610,12 @@ pub fn cornell_smoke() -> Scene {
         point3(165.0, 330.0, 165.0),
         white.clone(),
     );
-    let cube1_rotated = RotateY::new(Rc::new(cube1), 15.0);
-    let cube1_translated = Translate::new(Rc::new(cube1_rotated), vec3(265.0, 0.0, 295.0));
+    let cube1_transformed = Transform::new(
+        Rc::new(cube1),
+        Mat4::translation(vec3(265.0, 0.0, 295.0)) * Mat4::rotation_y(15.0),
+    );
     let cube1_smoke =
-        ConstantMedium::with_color(Rc::new(cube1_translated), 0.01, color(0.0, 0.0, 0.0));
+        ConstantMedium::with_color(Rc::new(cube1_transformed), 0.01, color(0.0, 0.0, 0.0));
     world.push(Rc::new(cube1_smoke));
 
     let cube2 = Cube::new(
@@ -354,19 +623,25 @@ pub fn cornell_smoke() -> Scene {
         point3(165.0, 165.0, 165.0),
         white.clone(),
     );
-    let cube2_rotated = RotateY::new(Rc::new(cube2), -18.0);
-    let cube2_translated = Translate::new(Rc::new(cube2_rotated), vec3(130.0, 0.0, 65.0));
+    let cube2_transformed = Transform::new(
+        Rc::new(cube2),
+        Mat4::translation(vec3(130.0, 0.0, 65.0)) * Mat4::rotation_y(-18.0),
+    );
     let cube2_smoke =
-        ConstantMedium::with_color(Rc::new(cube2_translated), 0.01, color(1.0, 1.0, 1.0));
+        ConstantMedium::with_color(Rc::new(cube2_transformed), 0.01, color(1.0, 1.0, 1.0));
     world.push(Rc::new(cube2_smoke));
 
     Scene {
         world,
+        names: HashMap::new(),
         samples_per_pixel: 200,
+        accelerator: AcceleratorKind::Bvh,
         background: color(0.0, 0.0, 0.0),
         lookfrom: point3(278.0, 278.0, -800.0),
         lookat: point3(278.0, 278.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 40.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
     }
 }
@@ -376,28 +651,14 @@ pub fn final_scene_the_next_week() -> Scene {
 
     let ground = Rc::new(Lambertian::new(color(0.48, 0.83, 0.53)));
 
-    const BOXES_PER_SIDE: usize = 20;
-    let mut boxes1: Vec<HittableInstance> = Vec::with_capacity(BOXES_PER_SIDE * BOXES_PER_SIDE);
-
-    for i in 0..BOXES_PER_SIDE {
-        for j in 0..BOXES_PER_SIDE {
-            let w = 100.0;
-            let x0 = -1000.0 + i as f32 * w;
-            let z0 = -1000.0 + j as f32 * w;
-            let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = super::random_range(1.0, 101.0);
-            let z1 = z0 + w;
-
-            boxes1.push(Rc::new(Cube::new(
-                point3(x0, y0, z0),
-                point3(x1, y1, z1),
-                ground.clone(),
-            )));
-        }
-    }
-
-    world.push(Rc::new(BVHNode::new(boxes1.as_slice(), 0.0, 1.0)));
+    world.push(Rc::new(HeightField::from_perlin(
+        point3(-1000.0, 0.0, -1000.0),
+        vec3(2000.0, 100.0, 2000.0),
+        201,
+        201,
+        0.003,
+        ground,
+    )));
 
     let light = Rc::new(DiffuseLight::new(color(7.0, 7.0, 7.0)));
     world.push(Rc::new(rect::XZ::new(
@@ -464,32 +725,50 @@ pub fn final_scene_the_next_week() -> Scene {
         Rc::new(Lambertian::new_from_texture(pertext)),
     )));
 
-    let mut boxes2: Vec<HittableInstance> = Vec::with_capacity(1000);
+    // A block of 125 spheres with a BVH of its own, placed in each octant
+    // of the cluster and turned a different way each time, so the thousand
+    // spheres share the memory of 125.
     let white = Rc::new(Lambertian::new(color(0.73, 0.73, 0.73)));
-
-    for _ in 0..1000 {
-        boxes2.push(Rc::new(Sphere::new(
-            Point3::random_range(0.0, 165.0),
-            10.0,
-            white.clone(),
-        )));
-    }
-
-    world.push(Rc::new(Translate::new(
-        Rc::new(RotateY::new(
-            Rc::new(BVHNode::new(boxes2.as_slice(), 0.0, 1.0)),
-            15.0,
-        )),
-        vec3(-100.0, 270.0, 395.0),
+    let balls: Vec<HittableInstance> = (0..125)
+        .map(|_| -> HittableInstance {
+            Rc::new(Sphere::new(
+                Point3::random_range(0.0, 82.5),
+                10.0,
+                white.clone(),
+            ))
+        })
+        .collect();
+    let block: HittableInstance = Rc::new(BVHNode::new(&balls, 0.0, 1.0));
+
+    let middle = vec3(41.25, 41.25, 41.25);
+    let boxes2: Vec<HittableInstance> = (0..8)
+        .map(|i| -> HittableInstance {
+            let corner = 82.5 * vec3((i & 1) as Float, (i >> 1 & 1) as Float, (i >> 2) as Float);
+            Rc::new(Transform::new(
+                block.clone(),
+                Mat4::translation(corner + middle)
+                    * Mat4::rotation_y(90.0 * i as Float)
+                    * Mat4::translation(-middle),
+            ))
+        })
+        .collect();
+
+    world.push(Rc::new(Transform::new(
+        Rc::new(BVHNode::new(boxes2.as_slice(), 0.0, 1.0)),
+        Mat4::translation(vec3(-100.0, 270.0, 395.0)) * Mat4::rotation_y(15.0),
     )));
 
     Scene {
         world,
+        names: HashMap::new(),
         samples_per_pixel: 10_000,
+        accelerator: AcceleratorKind::Bvh,
         background: color(0.0, 0.0, 0.0),
         lookfrom: point3(478.0, 278.0, -600.0),
         lookat: point3(278.0, 278.0, 0.0),
+        vup: vec3(0.0, 1.0, 0.0),
         vfov: 40.0,
+        aspect_ratio: 1.0,
         aperture: APERTURE,
     }
 }
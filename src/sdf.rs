@@ -0,0 +1,572 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::rc::Rc;
+
+const MAX_STEPS: usize = 256;
+const HIT_DISTANCE: Float = 1e-4;
+const NORMAL_DELTA: Float = 1e-3;
+
+// A signed distance function: negative inside the shape, positive outside,
+// and never more than the distance to the surface.
+pub trait Sdf {
+    fn distance(&self, p: Point3) -> Float;
+}
+
+pub type SdfInstance = Rc<dyn Sdf>;
+
+// Any closure can be used as a distance function.
+impl<F: Fn(Point3) -> Float> Sdf for F {
+    #[inline]
+    fn distance(&self, p: Point3) -> Float {
+        self(p)
+    }
+}
+
+// Ray marches (sphere traces) a distance function inside a user supplied
+// bounding box.  The uvs are always zero.
+pub struct SdfShape {
+    sdf: SdfInstance,
+    bbox: AABB,
+    step_scale: Float,
+    material: Rc<dyn Material>,
+}
+
+impl SdfShape {
+    pub fn new(sdf: SdfInstance, bbox: AABB, material: Rc<dyn Material>) -> Self {
+        Self {
+            sdf,
+            bbox,
+            step_scale: 1.0,
+            material,
+        }
+    }
+
+    // Shorten each marching step, for distance functions that overestimate
+    // the distance to the surface such as Twist.
+    pub fn with_step_scale(self, step_scale: Float) -> Self {
+        Self { step_scale, ..self }
+    }
+
+    // The normal is the gradient of the field, estimated with the
+    // tetrahedron technique.
+    fn normal(&self, p: Point3) -> Vec3 {
+        let h = NORMAL_DELTA;
+        let k = [
+            vec3(1.0, -1.0, -1.0),
+            vec3(-1.0, -1.0, 1.0),
+            vec3(-1.0, 1.0, -1.0),
+            vec3(1.0, 1.0, 1.0),
+        ];
+
+        k.iter()
+            .fold(vec3(0.0, 0.0, 0.0), |n, &k| {
+                n + k * self.sdf.distance(p + h * k)
+            })
+            .unit()
+    }
+
+    // The t where the march reaches the surface.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let (enter, exit) = self.bbox.clip(r, t_min, t_max)?;
+        let length = r.direction.length();
+
+        let mut t = enter;
+
+        // March towards the surface from whichever side the ray is on, so
+        // that refracted rays can leave the shape.
+        let side = self.sdf.distance(r.at(t)).signum();
+
+        for _ in 0..MAX_STEPS {
+            let distance = side * self.sdf.distance(r.at(t));
+
+            if distance < HIT_DISTANCE {
+                return Some(t);
+            }
+
+            t += self.step_scale * distance / length;
+            if t > exit {
+                break;
+            }
+        }
+
+        None
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let t = self.intersect(r, t_min, t_max)?;
+        let p = r.at(t);
+        let outward_normal = self.normal(p);
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        Some(HitRecord {
+            p,
+            // The march stops anywhere within HIT_DISTANCE of the surface.
+            // Twice that moves rays spawned from here more than HIT_DISTANCE
+            // past it on either side, so their march does not stop on the
+            // surface they leave.
+            p_error: 2.0 * HIT_DISTANCE * vec3(1.0, 1.0, 1.0) + ray_point_error(r, t),
+            normal,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+pub struct SdfSphere {
+    pub radius: Float,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Point3) -> Float {
+        p.length() - self.radius
+    }
+}
+
+// A box centered on the origin.
+pub struct SdfBox {
+    pub half_extents: Vec3,
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Point3) -> Float {
+        box_distance(p, self.half_extents)
+    }
+}
+
+// A box centered on the origin, with its edges rounded by `radius`.
+pub struct SdfRoundBox {
+    pub half_extents: Vec3,
+    pub radius: Float,
+}
+
+impl Sdf for SdfRoundBox {
+    fn distance(&self, p: Point3) -> Float {
+        let r = self.radius;
+        box_distance(p, self.half_extents - vec3(r, r, r)) - r
+    }
+}
+
+#[inline]
+fn box_distance(p: Point3, half_extents: Vec3) -> Float {
+    let q = vec3(
+        p.x.abs() - half_extents.x,
+        p.y.abs() - half_extents.y,
+        p.z.abs() - half_extents.z,
+    );
+    let outside = vec3(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+
+    outside.length() + q.x.max(q.y.max(q.z)).min(0.0)
+}
+
+// A torus around the Y axis, centered on the origin.
+pub struct SdfTorus {
+    pub major_radius: Float,
+    pub minor_radius: Float,
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Point3) -> Float {
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring * ring + p.y * p.y).sqrt() - self.minor_radius
+    }
+}
+
+pub struct SdfCapsule {
+    pub a: Point3,
+    pub b: Point3,
+    pub radius: Float,
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: Point3) -> Float {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(&ba) / ba.length_squared()).clamp(0.0, 1.0);
+
+        (pa - h * ba).length() - self.radius
+    }
+}
+
+// The Mandelbulb fractal, using its distance estimator.  Each iteration
+// raises z to `power`, which sets how many lobes the bulb has (8 for the
+// usual one).  More `iterations` show finer detail, but cost more per step.
+pub struct Mandelbulb {
+    pub power: Float,
+    pub iterations: usize,
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Point3) -> Float {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = 0.0;
+
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > 2.0 {
+                break;
+            }
+
+            // Any power of zero is zero, but its angles are undefined.
+            if r == 0.0 {
+                z = p;
+                dr = 1.0;
+                continue;
+            }
+
+            // Raise z to the power in spherical coordinates.
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            let zr = r.powf(self.power);
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+
+            z =
+                zr * vec3(
+                    theta.sin() * phi.cos(),
+                    phi.sin() * theta.sin(),
+                    theta.cos(),
+                ) + p;
+        }
+
+        // r ln r tends to zero with r.
+        if r == 0.0 {
+            return 0.0;
+        }
+
+        0.5 * r.ln() * r / dr
+    }
+}
+
+// Blends two shapes together over a distance of about `k`.
+pub struct SmoothUnion {
+    pub a: SdfInstance,
+    pub b: SdfInstance,
+    pub k: Float,
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Point3) -> Float {
+        let a = self.a.distance(p);
+        let b = self.b.distance(p);
+
+        if self.k <= 0.0 {
+            return a.min(b);
+        }
+
+        let h = (0.5 + 0.5 * (b - a) / self.k).clamp(0.0, 1.0);
+        b + h * (a - b) - self.k * h * (1.0 - h)
+    }
+}
+
+// Carves `b` out of `a`.
+pub struct Subtract {
+    pub a: SdfInstance,
+    pub b: SdfInstance,
+}
+
+impl Sdf for Subtract {
+    fn distance(&self, p: Point3) -> Float {
+        self.a.distance(p).max(-self.b.distance(p))
+    }
+}
+
+// Infinitely repeats a shape with the given period along each axis.  A zero
+// period leaves that axis alone.  The shape should fit in one cell.
+pub struct Repeat {
+    pub sdf: SdfInstance,
+    pub period: Vec3,
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Point3) -> Float {
+        let mut q = p;
+
+        for a in 0..3 {
+            let period = self.period[a];
+            if period > 0.0 {
+                q[a] = p[a] - period * (p[a] / period).round();
+            }
+        }
+
+        self.sdf.distance(q)
+    }
+}
+
+// Twists a shape around the Y axis by `rate` radians per unit of height.
+// This stretches space, so use SdfShape::with_step_scale to march it.
+pub struct Twist {
+    pub sdf: SdfInstance,
+    pub rate: Float,
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: Point3) -> Float {
+        let angle = self.rate * p.y;
+        let (sin, cos) = angle.sin_cos();
+
+        self.sdf
+            .distance(point3(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_distance_is_exact() {
+        let b = SdfBox {
+            half_extents: vec3(1.0, 1.0, 1.0),
+        };
+
+        assert_eq!(b.distance(point3(3.0, 0.0, 0.0)), 2.0);
+        assert_eq!(b.distance(point3(0.0, 0.5, 0.0)), -0.5);
+        assert_eq!(b.distance(point3(2.0, 2.0, 1.0)), Float::sqrt(2.0));
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let near = |a: Float, b: Float| (a - b).abs() < 1e-5;
+
+        let round = SdfRoundBox {
+            half_extents: vec3(1.0, 1.0, 1.0),
+            radius: 0.25,
+        };
+        assert!(near(round.distance(point3(3.0, 0.0, 0.0)), 2.0));
+        assert!(near(round.distance(point3(0.0, 0.0, 0.0)), -1.0));
+        // Further from the rounded edge than from a sharp one.
+        let edge = Float::sqrt(2.0) * 1.25 - 0.25;
+        assert!(near(round.distance(point3(2.0, 2.0, 0.0)), edge));
+
+        let torus = SdfTorus {
+            major_radius: 2.0,
+            minor_radius: 0.5,
+        };
+        assert!(near(torus.distance(point3(2.0, 0.0, 0.0)), -0.5));
+        assert!(near(torus.distance(point3(0.0, 0.0, 0.0)), 1.5));
+        assert!(near(torus.distance(point3(0.0, 1.0, -2.0)), 0.5));
+
+        let capsule = SdfCapsule {
+            a: point3(0.0, 0.0, 0.0),
+            b: point3(0.0, 2.0, 0.0),
+            radius: 0.5,
+        };
+        assert!(near(capsule.distance(point3(1.0, 1.0, 0.0)), 0.5));
+        assert!(near(capsule.distance(point3(0.0, 3.0, 0.0)), 0.5));
+        assert!(near(capsule.distance(point3(0.0, -1.0, 0.0)), 0.5));
+        assert!(near(capsule.distance(point3(0.0, 1.0, 0.0)), -0.5));
+    }
+
+    #[test]
+    fn repeat_and_twist_move_space() {
+        let near = |a: Float, b: Float| (a - b).abs() < 1e-5;
+
+        // Copies along x only.
+        let row = Repeat {
+            sdf: Rc::new(SdfSphere { radius: 0.5 }),
+            period: vec3(2.0, 0.0, 0.0),
+        };
+        assert!(near(row.distance(point3(4.7, 0.0, 0.0)), 0.2));
+        assert!(near(row.distance(point3(-4.0, 0.0, 0.0)), -0.5));
+        assert!(near(row.distance(point3(0.0, 3.0, 0.0)), 2.5));
+
+        // A quarter turn per unit of height.
+        let twisted = Twist {
+            sdf: Rc::new(SdfBox {
+                half_extents: vec3(1.0, 2.0, 0.25),
+            }),
+            rate: std::f64::consts::FRAC_PI_2 as Float,
+        };
+        assert!(near(twisted.distance(point3(0.0, 0.0, 1.5)), 1.25));
+        assert!(near(twisted.distance(point3(0.0, 1.0, 1.5)), 0.5));
+    }
+
+    #[test]
+    fn marches_through_repeat_and_twist() {
+        let material = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+
+        // Down onto the copy at x = 4, not the one at the origin.
+        let row = SdfShape::new(
+            Rc::new(Repeat {
+                sdf: Rc::new(SdfSphere { radius: 0.5 }),
+                period: vec3(2.0, 0.0, 0.0),
+            }),
+            aabb(point3(-1.0, -1.0, -1.0), point3(5.0, 1.0, 1.0)),
+            material.clone(),
+        );
+        let r = Ray::new(point3(4.2, 0.0, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = row.hit(&r, 0.0, Float::INFINITY).unwrap();
+        let expected = 5.0 - Float::sqrt(0.25 - 0.04);
+        assert!((rec.t - expected).abs() < 1e-3, "{}", rec.t);
+        assert!((rec.normal - vec3(0.4, 0.0, 2.0 * (5.0 - expected))).length() < 1e-2);
+
+        // Half way up, the box has turned an eighth, so its thin side is
+        // diagonal and reaches further along z.
+        let twisted = SdfShape::new(
+            Rc::new(Twist {
+                sdf: Rc::new(SdfBox {
+                    half_extents: vec3(1.0, 2.0, 0.25),
+                }),
+                rate: std::f64::consts::FRAC_PI_2 as Float,
+            }),
+            aabb(point3(-1.5, -2.0, -1.5), point3(1.5, 2.0, 1.5)),
+            material,
+        )
+        .with_step_scale(0.5);
+        let r = Ray::new(point3(0.0, 0.5, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = twisted.hit(&r, 0.0, Float::INFINITY).unwrap();
+        let expected = 5.0 - 0.25 * Float::sqrt(2.0);
+        assert!((rec.t - expected).abs() < 1e-3, "{}", rec.t);
+    }
+
+    #[test]
+    fn subtract_carves_a_hole() {
+        let shape = Subtract {
+            a: Rc::new(SdfBox {
+                half_extents: vec3(1.0, 1.0, 1.0),
+            }),
+            b: Rc::new(SdfSphere { radius: 0.5 }),
+        };
+
+        assert!(shape.distance(point3(0.0, 0.0, 0.0)) > 0.0);
+        assert!(shape.distance(point3(0.0, 0.9, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn smooth_union_blends_below_both() {
+        let shape = SmoothUnion {
+            a: Rc::new(|p: Point3| (p - point3(-1.0, 0.0, 0.0)).length() - 0.9),
+            b: Rc::new(|p: Point3| (p - point3(1.0, 0.0, 0.0)).length() - 0.9),
+            k: 0.5,
+        };
+
+        // Halfway between the spheres, the blend fills the gap.
+        assert!(shape.distance(point3(0.0, 0.0, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn mandelbulb_is_finite_at_its_center() {
+        let bulb = Mandelbulb {
+            power: 8.0,
+            iterations: 10,
+        };
+
+        assert_eq!(bulb.distance(point3(0.0, 0.0, 0.0)), 0.0);
+        assert!(bulb.distance(point3(0.0, 0.0, 0.1)) <= 0.0);
+        assert!(bulb.distance(point3(0.0, 3.0, 0.0)) > 0.5);
+
+        // Marches from outside hit it, and one from the center stops there
+        // rather than stepping by NaN.
+        let shape = SdfShape::new(
+            Rc::new(bulb),
+            aabb(point3(-1.5, -1.5, -1.5), point3(1.5, 1.5, 1.5)),
+            Rc::new(Lambertian::new(color(0.5, 0.5, 0.5))),
+        );
+        let r = Ray::new(point3(0.0, 0.0, 3.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(shape.hit(&r, 0.0, Float::INFINITY).is_some());
+        let r = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert_eq!(shape.hit(&r, 0.0, Float::INFINITY).unwrap().t, 0.0);
+    }
+
+    #[test]
+    fn marches_to_surface() {
+        let shape = SdfShape::new(
+            Rc::new(SdfSphere { radius: 1.0 }),
+            aabb(point3(-1.0, -1.0, -1.0), point3(1.0, 1.0, 1.0)),
+            Rc::new(Lambertian::new(color(0.5, 0.5, 0.5))),
+        );
+
+        let r = Ray::new(point3(0.0, 0.0, 5.0), vec3(0.0, 0.0, -2.0), 0.0);
+        let rec = shape.hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert!((rec.t - 2.0).abs() < 1e-3);
+        assert!((rec.normal - vec3(0.0, 0.0, 1.0)).length() < 1e-3);
+        assert!(rec.front_face);
+
+        // And back out again from the inside.
+        let r = Ray::new(point3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let rec = shape.hit(&r, 0.001, Float::INFINITY).unwrap();
+
+        assert!((rec.t - 1.0).abs() < 1e-3);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn spawned_rays_leave_the_surface() {
+        let shape = SdfShape::new(
+            Rc::new(SdfSphere { radius: 1.0 }),
+            aabb(point3(-1.0, -1.0, -1.0), point3(1.0, 1.0, 1.0)),
+            Rc::new(Lambertian::new(color(0.5, 0.5, 0.5))),
+        );
+
+        for i in 0..200 {
+            let f = i as Float;
+            let d = vec3((f * 0.37).sin(), (f * 0.73).cos(), (f * 1.31).sin() + 0.1).unit();
+            let r = Ray::new(-3.0 * d, d, 0.0);
+            let rec = shape.hit(&r, 0.0, Float::INFINITY).unwrap();
+
+            // Back out along the normal, and into the sphere across it.
+            let out = rec.spawn_ray(rec.normal, 0.0);
+            assert!(shape.hit(&out, 0.0, Float::INFINITY).is_none());
+            let through = shape.hit(&rec.spawn_ray(d, 0.0), 0.0, Float::INFINITY);
+            assert!(through.unwrap().t > 1.9);
+        }
+
+        // A ray that merely starts close to the surface, rather than being
+        // spawned from it, still hits it.
+        let r = Ray::new(point3(0.0, 0.0, 1.00005), vec3(0.0, 0.0, -1.0), 0.0);
+        let rec = shape.hit(&r, 0.0, Float::INFINITY).unwrap();
+        assert!(rec.t < 1e-4);
+    }
+
+    #[test]
+    fn refracts_through_itself() {
+        let shape = SdfShape::new(
+            Rc::new(SdfSphere { radius: 1.0 }),
+            aabb(point3(-1.0, -1.0, -1.0), point3(1.0, 1.0, 1.0)),
+            Rc::new(Dielectric::new(1.5)),
+        );
+
+        for i in 0..50 {
+            let x = i as Float / 50.0 - 0.5;
+            let r = Ray::new(point3(x, 0.3 * x, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+            let rec = shape.hit(&r, 0.0, Float::INFINITY).unwrap();
+
+            for _ in 0..10 {
+                let (_, scattered) = rec.material.scatter(&r, &rec).unwrap();
+                let next = shape.hit(&scattered, 0.0, Float::INFINITY);
+
+                // Refracted rays cross the sphere before they leave it, and
+                // reflected ones never come back.
+                if scattered.direction.dot(&rec.geometric_normal) < 0.0 {
+                    let next = next.unwrap();
+                    assert!(next.t > 1.0, "{} at {}", next.t, x);
+                    assert!(!next.front_face);
+                } else {
+                    assert!(next.is_none());
+                }
+            }
+        }
+    }
+}
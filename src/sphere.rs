@@ -4,17 +4,17 @@ use crate::material::*;
 use crate::ray::*;
 use crate::vec3::*;
 
-use std::f32::consts::PI;
+use crate::vec3::consts::PI;
 use std::rc::Rc;
 
 pub struct Sphere {
     pub center: Point3,
-    pub radius: f32,
+    pub radius: Float,
     pub material: Rc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f32, material: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, radius: Float, material: Rc<dyn Material>) -> Self {
         Self {
             center,
             radius,
@@ -22,77 +22,120 @@ impl Sphere {
         }
     }
 
-    fn get_uv(p: &Point3) -> (f32, f32) {
+    fn get_uv(p: &Point3) -> (Float, Float) {
         // p: a given point on the sphere of radius one, centered at the origin.
         // u: returned value [0,1] of angle around the Y axis from X=-1.
         // v: returned value [0,1] of angle from Y=-1 to Y=+1.
         //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
         //     <0 1 0> yields <0.50 1.00>       < 0 -1  0> yields <0.50 0.00>
         //     <0 0 1> yields <0.25 0.50>       < 0  0 -1> yields <0.75 0.50>
-        let theta = f32::acos(-p.y);
-        let phi = f32::atan2(-p.z, p.x) + PI;
+        let theta = Float::acos(-p.y);
+        let phi = Float::atan2(-p.z, p.x) + PI;
 
         let u = phi / (2.0 * PI);
         let v = theta / PI;
 
         (u, v)
     }
-}
-
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.origin - self.center;
-        let a = r.direction.length_squared();
-        let half_b = oc.dot(&r.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-
-        let sqrtd = discriminant.sqrt();
 
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
-
-        let p = r.at(root);
+    fn record(&self, r: &Ray, t: Float) -> HitRecord<'_> {
+        let (p, p_error) = reproject(r.at(t), self.center, self.radius);
         let outward_normal = (p - self.center) / self.radius;
         let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
 
         let (u, v) = Sphere::get_uv(&outward_normal);
 
-        let rec = HitRecord {
+        HitRecord {
             p,
+            p_error,
             normal,
-            material: self.material.clone(),
-            t: root,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
             u,
             v,
             front_face,
-        };
+            face: 0,
+            mesh: None,
+            object: None,
+        }
+    }
+}
 
-        Some(rec)
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let root = nearest_root(r, self.center, self.radius, t_min, t_max)?;
+
+        Some(self.record(r, root))
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        nearest_root(r, self.center, self.radius, t_min, t_max).is_some()
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
         let radius = vec3(self.radius, self.radius, self.radius);
         Some(aabb(self.center - radius, self.center + radius))
     }
+
+    fn crossings(&self, r: &Ray, t_min: Float, t_max: Float) -> Vec<HitRecord<'_>> {
+        roots(r, self.center, self.radius)
+            .into_iter()
+            .flat_map(|(near, far)| [near, far])
+            .filter(|t| (t_min..=t_max).contains(t))
+            .map(|t| self.record(r, t))
+            .collect()
+    }
+}
+
+// Where the ray enters and leaves the sphere.
+fn roots(r: &Ray, center: Point3, radius: Float) -> Option<(Float, Float)> {
+    let oc = r.origin - center;
+    let a = r.direction.length_squared();
+    let half_b = oc.dot(&r.direction);
+    let c = oc.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrtd = discriminant.sqrt();
+
+    Some(((-half_b - sqrtd) / a, (-half_b + sqrtd) / a))
+}
+
+// The nearest root that lies in [t_min, t_max].
+fn nearest_root(
+    r: &Ray,
+    center: Point3,
+    radius: Float,
+    t_min: Float,
+    t_max: Float,
+) -> Option<Float> {
+    let (near, far) = roots(r, center, radius)?;
+
+    [near, far]
+        .into_iter()
+        .find(|t| (t_min..=t_max).contains(t))
+}
+
+// Move a point near the sphere onto it, which leaves much less error than
+// the roots of the quadratic, and bound that error.
+fn reproject(p: Point3, center: Point3, radius: Float) -> (Point3, Vec3) {
+    let offset = p - center;
+    let offset = offset * (radius / offset.length());
+    let p = center + offset;
+
+    (p, gamma(5) * offset.abs() + gamma(1) * p.abs())
 }
 
 pub struct MovingSphere {
     pub center0: Point3,
     pub center1: Point3,
-    pub time0: f32,
-    pub time1: f32,
-    pub radius: f32,
+    pub time0: Float,
+    pub time1: Float,
+    pub radius: Float,
     pub material: Rc<dyn Material>,
 }
 
@@ -100,9 +143,9 @@ impl MovingSphere {
     pub fn new(
         center0: Point3,
         center1: Point3,
-        time0: f32,
-        time1: f32,
-        radius: f32,
+        time0: Float,
+        time1: Float,
+        radius: Float,
         material: Rc<dyn Material>,
     ) -> Self {
         Self {
@@ -115,54 +158,45 @@ impl MovingSphere {
         }
     }
 
-    fn center(&self, time: f32) -> Point3 {
+    fn center(&self, time: Float) -> Point3 {
         self.center0
             + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
     }
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.origin - self.center(r.time);
-        let a = r.direction.length_squared();
-        let half_b = oc.dot(&r.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-
-        let sqrtd = discriminant.sqrt();
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let center = self.center(r.time);
+        let root = nearest_root(r, center, self.radius, t_min, t_max)?;
 
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
-
-        let p = r.at(root);
-        let outward_normal = (p - self.center(r.time)) / self.radius;
+        let (p, p_error) = reproject(r.at(root), center, self.radius);
+        let outward_normal = (p - center) / self.radius;
         let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
         let (u, v) = Sphere::get_uv(&outward_normal);
 
         let rec = HitRecord {
             p,
+            p_error,
             normal,
-            material: self.material.clone(),
+            geometric_normal: outward_normal,
+            material: &*self.material,
             t: root,
             u,
             v,
             front_face,
+            face: 0,
+            mesh: None,
+            object: None,
         };
 
         Some(rec)
     }
 
-    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        nearest_root(r, self.center(r.time), self.radius, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, time0: Float, time1: Float) -> Option<AABB> {
         let radius = vec3(self.radius, self.radius, self.radius);
         let box0 = aabb(self.center(time0) - radius, self.center(time0) + radius);
         let box1 = aabb(self.center(time1) - radius, self.center(time1) + radius);
@@ -1,3 +1,5 @@
+use crate::hittable::HitRecord;
+use crate::mesh::MeshData;
 use crate::perlin::*;
 use crate::vec3::*;
 use jpeg_decoder::Decoder;
@@ -6,7 +8,14 @@ use std::io::BufReader;
 use std::rc::Rc;
 
 pub trait Texture {
-    fn value(&self, u: f32, v: f32, p: &Point3) -> Color;
+    fn value(&self, u: Float, v: Float, p: &Point3) -> Color;
+
+    // The color at a hit.  Textures that need more of the hit than its uvs
+    // and point, such as VertexColorTexture, override this.
+    #[inline]
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 pub struct SolidColor {
@@ -15,7 +24,7 @@ pub struct SolidColor {
 
 impl SolidColor {
     #[inline]
-    pub fn rgb(red: f32, green: f32, blue: f32) -> Self {
+    pub fn rgb(red: Float, green: Float, blue: Float) -> Self {
         Self::new(color(red, green, blue))
     }
 
@@ -27,11 +36,35 @@ impl SolidColor {
 
 impl Texture for SolidColor {
     #[inline]
-    fn value(&self, _u: f32, _v: f32, _p: &Point3) -> Color {
+    fn value(&self, _u: Float, _v: Float, _p: &Point3) -> Color {
         self.color_value
     }
 }
 
+// Another texture multiplied by a color.
+pub struct ScaledTexture {
+    texture: Rc<dyn Texture>,
+    scale: Color,
+}
+
+impl ScaledTexture {
+    pub fn new(texture: Rc<dyn Texture>, scale: Color) -> Self {
+        Self { texture, scale }
+    }
+}
+
+impl Texture for ScaledTexture {
+    #[inline]
+    fn value(&self, u: Float, v: Float, p: &Point3) -> Color {
+        self.scale * self.texture.value(u, v, p)
+    }
+
+    #[inline]
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.scale * self.texture.value_at(rec)
+    }
+}
+
 pub struct CheckerTexture {
     odd: Rc<dyn Texture>,
     even: Rc<dyn Texture>,
@@ -52,7 +85,7 @@ impl CheckerTexture {
 
 impl Texture for CheckerTexture {
     #[inline]
-    fn value(&self, u: f32, v: f32, p: &Point3) -> Color {
+    fn value(&self, u: Float, v: Float, p: &Point3) -> Color {
         let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
         if sines < 0.0 {
             self.odd.value(u, v, p)
@@ -64,11 +97,11 @@ impl Texture for CheckerTexture {
 
 pub struct NoiseTexture {
     noise: Perlin,
-    scale: f32,
+    scale: Float,
 }
 
 impl NoiseTexture {
-    pub fn new(scale: f32) -> Self {
+    pub fn new(scale: Float) -> Self {
         Self {
             noise: Perlin::new(),
             scale,
@@ -78,15 +111,15 @@ impl NoiseTexture {
 
 impl Texture for NoiseTexture {
     #[inline]
-    fn value(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+    fn value(&self, _u: Float, _v: Float, p: &Point3) -> Color {
         color(1.0, 1.0, 1.0) * 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin())
     }
 }
 
 pub struct ImageTexture {
     data: Vec<u8>,
-    width: u16,
-    height: u16,
+    width: u32,
+    height: u32,
     bytes_per_pixel: usize,
     bytes_per_scanline: usize,
 }
@@ -99,26 +132,43 @@ impl ImageTexture {
         let metadata = decoder.info().unwrap();
 
         let bytes_per_pixel = metadata.pixel_format.pixel_bytes();
-        let bytes_per_scanline = bytes_per_pixel * metadata.width as usize;
+
+        Self::from_pixels(
+            pixels,
+            metadata.width as u32,
+            metadata.height as u32,
+            bytes_per_pixel,
+        )
+    }
+
+    // Wrap already decoded 8-bit pixels, stored top row first.  The first
+    // three bytes of each pixel are used as red, green and blue.
+    pub fn from_pixels(data: Vec<u8>, width: u32, height: u32, bytes_per_pixel: usize) -> Self {
+        assert!(bytes_per_pixel >= 3, "Expected at least RGB pixels");
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * bytes_per_pixel,
+            "Pixel data does not match the image dimensions"
+        );
 
         Self {
-            data: pixels,
-            width: metadata.width,
-            height: metadata.height,
+            data,
+            width,
+            height,
             bytes_per_pixel,
-            bytes_per_scanline,
+            bytes_per_scanline: bytes_per_pixel * width as usize,
         }
     }
 }
 
 impl Texture for ImageTexture {
-    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+    fn value(&self, u: Float, v: Float, _p: &Point3) -> Color {
         // Clamp input texture coordinates to [0,1] x [1,0]
         let cu = u.clamp(0.0, 1.0);
         let cv = 1.0 - v.clamp(0.0, 1.0); // Flip V to image coordinates
 
-        let mut i = (cu * self.width as f32) as usize;
-        let mut j = (cv * self.height as f32) as usize;
+        let mut i = (cu * self.width as Float) as usize;
+        let mut j = (cv * self.height as Float) as usize;
 
         // Clamp integer mapping, since actual coordinates should be less than 1.0
         if i >= self.width as usize {
@@ -128,13 +178,55 @@ impl Texture for ImageTexture {
             j = (self.height - 1) as usize;
         }
 
-        const COLOR_SCALE: f32 = 1.0 / 255.0;
-        let offset = (j * self.bytes_per_scanline + i * self.bytes_per_pixel) as usize;
+        const COLOR_SCALE: Float = 1.0 / 255.0;
+        let offset = j * self.bytes_per_scanline + i * self.bytes_per_pixel;
 
         color(
-            COLOR_SCALE * self.data[offset] as f32,
-            COLOR_SCALE * self.data[offset + 1] as f32,
-            COLOR_SCALE * self.data[offset + 2] as f32,
+            COLOR_SCALE * self.data[offset] as Float,
+            COLOR_SCALE * self.data[offset + 1] as Float,
+            COLOR_SCALE * self.data[offset + 2] as Float,
         )
     }
 }
+
+// Interpolates the colors at the vertices of a mesh across the face that
+// was hit, using the barycentric coordinates of the hit as (u, v).  One
+// texture serves the whole mesh.
+pub struct VertexColorTexture {
+    data: Rc<MeshData>,
+    colors: Vec<Color>,
+}
+
+impl VertexColorTexture {
+    pub fn new(data: Rc<MeshData>, colors: Vec<Color>) -> Self {
+        assert_eq!(
+            colors.len(),
+            data.positions.len(),
+            "Expected one color per vertex"
+        );
+
+        Self { data, colors }
+    }
+}
+
+impl Texture for VertexColorTexture {
+    // Without the face, there is nothing to interpolate.
+    #[inline]
+    fn value(&self, _u: Float, _v: Float, _p: &Point3) -> Color {
+        color(0.0, 0.0, 0.0)
+    }
+
+    #[inline]
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        // Only hits on this mesh have a face and barycentrics to use, should
+        // the material be shared with anything else.
+        if !rec.mesh.is_some_and(|mesh| std::ptr::eq(mesh, &*self.data)) {
+            return self.value(rec.u, rec.v, &rec.p);
+        }
+
+        let [i0, i1, i2] = self.data.indices[rec.face as usize];
+        let (u, v) = (rec.u, rec.v);
+
+        (1.0 - u - v) * self.colors[i0] + u * self.colors[i1] + v * self.colors[i2]
+    }
+}
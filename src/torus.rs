@@ -0,0 +1,326 @@
+// The quartic is solved in f64 whatever Float is, so with the "f64" feature
+// the casts below do nothing.
+#![cfg_attr(feature = "f64", allow(clippy::unnecessary_cast))]
+
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::f64::consts::PI as PI64;
+use std::rc::Rc;
+
+// A torus around the Y axis: a tube of `minor_radius` swept around a circle
+// of `major_radius` centered on `center`.
+pub struct Torus {
+    center: Point3,
+    major_radius: Float,
+    minor_radius: Float,
+    material: Rc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        major_radius: Float,
+        minor_radius: Float,
+        material: Rc<dyn Material>,
+    ) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    // The t of the nearest hit.
+    fn intersect(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<Float> {
+        let length = r.direction.length();
+        let d = r.direction / length;
+        let o = r.origin - self.center;
+
+        // Work in distances along the unit direction and start from the
+        // bounding sphere: the quartic loses precision quickly when the
+        // origin is far away.
+        let bound = self.major_radius + self.minor_radius;
+        let half_b = o.dot(&d);
+        let c = o.length_squared() - bound * bound;
+        let discriminant = half_b * half_b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let start = (-half_b - discriminant.sqrt()).max(0.0);
+        let o = o + start * d;
+
+        let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        let a2 = (self.major_radius as f64).powi(2);
+        let b2 = (self.minor_radius as f64).powi(2);
+
+        let e = ox * ox + oy * oy + oz * oz - a2 - b2;
+        let f = ox * dx + oy * dy + oz * dz;
+        let four_a2 = 4.0 * a2;
+
+        let coefficients = [
+            e * e - four_a2 * (b2 - oy * oy),
+            4.0 * f * e + 2.0 * four_a2 * oy * dy,
+            2.0 * e + 4.0 * f * f + four_a2 * dy * dy,
+            4.0 * f,
+            1.0,
+        ];
+
+        solve_quartic(&coefficients)
+            .into_iter()
+            .map(|s| (start + s as Float) / length)
+            .filter(|t| (t_min..=t_max).contains(t))
+            .min_by(Float::total_cmp)
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let t = self.intersect(r, t_min, t_max)?;
+
+        let local = r.at(t) - self.center;
+
+        // The normal points away from the nearest point on the center circle
+        // of the tube.
+        let ring = vec3(local.x, 0.0, local.z);
+        let ring_distance = ring.length();
+        let ring_point = self.major_radius / ring_distance * ring;
+        let outward_normal = (local - ring_point).unit();
+
+        // Put the point back on the surface, to bound its error.
+        let tube = self.minor_radius * outward_normal;
+        let p = self.center + ring_point + tube;
+        let p_error = gamma(6) * (ring_point.abs() + tube.abs()) + gamma(2) * p.abs();
+
+        let phi = (local.z as f64).atan2(local.x as f64) + PI64;
+        let theta = (local.y as f64).atan2((ring_distance - self.major_radius) as f64) + PI64;
+        let u = (phi / (2.0 * PI64)) as Float;
+        let v = (theta / (2.0 * PI64)) as Float;
+
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
+            u,
+            v,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = vec3(outer, self.minor_radius, outer);
+
+        Some(aabb(self.center - extent, self.center + extent))
+    }
+}
+
+const EQN_EPSILON: f64 = 1e-9;
+
+#[inline]
+fn is_zero(x: f64) -> bool {
+    x.abs() < EQN_EPSILON
+}
+
+// The real roots of c[2] x^2 + c[1] x + c[0].
+fn solve_quadratic(c: &[f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+
+    if is_zero(d) {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+// The real roots of c[3] x^3 + ... + c[0], using Cardano's formula.
+fn solve_cubic(c: &[f64; 4]) -> Vec<f64> {
+    // Normal form x^3 + Ax^2 + Bx + C, then substitute x = y - A/3 to get
+    // y^3 + 3py + 2q.
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut roots = if is_zero(d) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // Three real roots.
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI64 / 3.0).cos(),
+            -t * (phi - PI64 / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+
+    roots
+}
+
+// The real roots of c[4] x^4 + ... + c[0], using Ferrari's method and then
+// polished with a few Newton iterations.
+fn solve_quartic(c: &[f64; 5]) -> Vec<f64> {
+    // Normal form x^4 + Ax^3 + Bx^2 + Cx + D, then substitute x = y - A/4
+    // to get y^4 + py^2 + qy + r.
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+
+    let mut roots = if is_zero(r) {
+        // No absolute term: y(y^3 + py + q) = 0
+        let mut roots = solve_cubic(&[q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // Take one root of the resolvent cubic to split the quartic into two
+        // quadratics.
+        let z = solve_cubic(&[r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+
+        let u = if is_zero(u) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+
+        let v = if is_zero(v) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+
+        let mut roots = solve_quadratic(&[z - u, if q < 0.0 { -v } else { v }, 1.0]);
+        roots.extend(solve_quadratic(&[z + u, if q < 0.0 { v } else { -v }, 1.0]));
+        roots
+    };
+
+    let f = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let df = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+
+        for _ in 0..2 {
+            let slope = df(*root);
+            if slope.abs() > EQN_EPSILON {
+                *root -= f(*root) / slope;
+            }
+        }
+    }
+
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(c: &[f64; 5], expected: &[f64]) {
+        let mut roots = solve_quartic(c);
+        roots.sort_by(f64::total_cmp);
+        roots.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-6, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn solves_quartics() {
+        // (x - 1)(x - 2)(x - 3)(x - 4), and twice that.
+        assert_roots(&[24.0, -50.0, 35.0, -10.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        assert_roots(&[48.0, -100.0, 70.0, -20.0, 2.0], &[1.0, 2.0, 3.0, 4.0]);
+
+        // (x^2 + 1)(x - 2)(x + 3): two of the roots are complex.
+        assert_roots(&[-6.0, 1.0, -5.0, 1.0, 1.0], &[-3.0, 2.0]);
+
+        // (x^2 - 1)^2 has double roots, and x^4 + 1 none at all.
+        assert_roots(&[1.0, 0.0, -2.0, 0.0, 1.0], &[-1.0, 1.0]);
+        assert_roots(&[1.0, 0.0, 0.0, 0.0, 1.0], &[]);
+
+        // x (x - 1)(x + 2)(x - 5), with no constant term.
+        assert_roots(&[0.0, 10.0, -7.0, -4.0, 1.0], &[-2.0, 0.0, 1.0, 5.0]);
+    }
+
+    #[test]
+    fn hits_the_tube() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(color(0.5, 0.5, 0.5)));
+        let torus = Torus::new(point3(0.0, 0.0, 0.0), 2.0, 0.5, material);
+
+        // From the side, onto the outside of the ring.
+        let r = Ray::new(point3(5.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let rec = torus.hit(&r, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-4);
+        assert!((rec.normal - vec3(1.0, 0.0, 0.0)).length() < 1e-4);
+
+        // From above, onto the top of the tube, with a direction that is
+        // not of unit length.
+        let r = Ray::new(point3(0.0, 5.0, -2.0), vec3(0.0, -2.0, 0.0), 0.0);
+        let rec = torus.hit(&r, 0.0, Float::INFINITY).unwrap();
+        assert!((rec.t - 2.25).abs() < 1e-4);
+        assert!((rec.normal - vec3(0.0, 1.0, 0.0)).length() < 1e-4);
+        assert!(rec.front_face);
+
+        // Through the hole, and past the top.
+        let hole = Ray::new(point3(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(torus.hit(&hole, 0.0, Float::INFINITY).is_none());
+        let over = Ray::new(point3(5.0, 0.6, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        assert!(torus.hit(&over, 0.0, Float::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::rc::Rc;
+
+// How parallel to the plane of a triangle a ray may be before it is taken to
+// miss, relative to the size of the triangle, so tiny triangles still work.
+const EPSILON: Float = 1e-8;
+
+// How much to widen the bounding box along any axis it is flat in, so that
+// it still has some volume.
+const PADDING: Float = 0.0001;
+
+pub struct Triangle {
+    pub v0: Point3,
+    pub v1: Point3,
+    pub v2: Point3,
+    pub material: Rc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Rc<dyn Material>) -> Self {
+        Self {
+            v0,
+            v1,
+            v2,
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(r, self.v0, self.v1, self.v2, t_min, t_max)?;
+
+        let outward_normal = (self.v1 - self.v0).cross(&(self.v2 - self.v0)).unit();
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        let (p, p_error) = barycentric_point(self.v0, self.v1, self.v2, b1, b2);
+
+        Some(HitRecord {
+            p,
+            p_error,
+            normal,
+            geometric_normal: outward_normal,
+            material: &*self.material,
+            t,
+            u: b1,
+            v: b2,
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        intersect(r, self.v0, self.v1, self.v2, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(bounding_box(self.v0, self.v1, self.v2))
+    }
+}
+
+// Möller-Trumbore ray/triangle intersection.  Returns the ray parameter and
+// the barycentric coordinates of v1 and v2 at the hit point.
+pub(crate) fn intersect(
+    r: &Ray,
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    t_min: Float,
+    t_max: Float,
+) -> Option<(Float, Float, Float)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+
+    let pvec = r.direction.cross(&edge2);
+    let det = edge1.dot(&pvec);
+
+    // The ray is parallel to the plane of the triangle.  det scales with
+    // both edges and the direction, so compare it to them rather than to a
+    // fixed size.
+    let scale = edge1.length_squared() * edge2.length_squared() * r.direction.length_squared();
+    if det * det < EPSILON * EPSILON * scale {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin - v0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&edge1);
+    let b2 = r.direction.dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+// The point at barycentric coordinates (b1, b2), and a bound on its error.
+// This is much tighter than for the point along the ray.
+pub(crate) fn barycentric_point(
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    b1: Float,
+    b2: Float,
+) -> (Point3, Vec3) {
+    let b0 = 1.0 - b1 - b2;
+    let p = b0 * v0 + b1 * v1 + b2 * v2;
+    let p_error = gamma(7) * ((b0 * v0).abs() + (b1 * v1).abs() + (b2 * v2).abs());
+
+    (p, p_error)
+}
+
+pub(crate) fn bounding_box(v0: Point3, v1: Point3, v2: Point3) -> AABB {
+    let mut min = v0;
+    let mut max = v0;
+
+    for v in [v1, v2] {
+        for a in 0..3 {
+            min[a] = min[a].min(v[a]);
+            max[a] = max[a].max(v[a]);
+        }
+    }
+
+    // Axis aligned triangles have a flat bounding box, so pad it the same way
+    // the rectangles do.
+    for a in 0..3 {
+        if max[a] - min[a] < PADDING {
+            min[a] -= PADDING;
+            max[a] += PADDING;
+        }
+    }
+
+    aabb(min, max)
+}
@@ -1,8 +1,33 @@
+// The impls for values forward to those for references, which clippy takes
+// for a needless `&`, but dropping it would call the same impl again.
+#![allow(clippy::op_ref)]
+
+// The floating point type of the whole renderer.  Build with the "f64"
+// feature for double precision, which helps large scenes where f32 runs out
+// of precision far from the origin.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+// A bound on the rounding error of n floating point operations in a row,
+// relative to the size of the result.
+#[inline]
+pub fn gamma(n: i32) -> Float {
+    let e = n as Float * Float::EPSILON * 0.5;
+    e / (1.0 - e)
+}
+
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub struct Vec3 {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
 impl Vec3 {
@@ -12,7 +37,7 @@ impl Vec3 {
     }
 
     #[inline]
-    pub fn random_range(min: f32, max: f32) -> Vec3 {
+    pub fn random_range(min: Float, max: Float) -> Vec3 {
         vec3(
             super::random_range(min, max),
             super::random_range(min, max),
@@ -21,7 +46,7 @@ impl Vec3 {
     }
 
     #[inline]
-    pub fn dot(&self, v: &Vec3) -> f32 {
+    pub fn dot(&self, v: &Vec3) -> Float {
         self.x * v.x + self.y * v.y + self.z * v.z
     }
 
@@ -39,10 +64,10 @@ impl Vec3 {
         self - 2.0 * self.dot(n) * n
     }
 
-    pub fn refract(&self, n: &Vec3, etai_over_etat: f32) -> Vec3 {
-        let cos_theta = f32::min(-self.dot(n), 1.0);
+    pub fn refract(&self, n: &Vec3, etai_over_etat: Float) -> Vec3 {
+        let cos_theta = Float::min(-self.dot(n), 1.0);
         let r_out_perp = etai_over_etat * (self + cos_theta * n);
-        let r_out_parallel = -1.0 * (1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
+        let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
 
         r_out_perp + r_out_parallel
     }
@@ -53,18 +78,23 @@ impl Vec3 {
     }
 
     #[inline]
-    pub fn length(&self) -> f32 {
+    pub fn length(&self) -> Float {
         self.length_squared().sqrt()
     }
 
     #[inline]
-    pub fn length_squared(&self) -> f32 {
+    pub fn length_squared(&self) -> Float {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
+    #[inline]
+    pub fn abs(&self) -> Vec3 {
+        vec3(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
     #[inline]
     pub fn near_zero(&self) -> bool {
-        const S: f32 = 1e-8;
+        const S: Float = 1e-8;
         self.x.abs() < S && self.y.abs() < S && self.z.abs() < S
     }
 }
@@ -73,17 +103,17 @@ pub type Point3 = Vec3;
 pub type Color = Vec3;
 
 #[inline(always)]
-pub const fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+pub const fn vec3(x: Float, y: Float, z: Float) -> Vec3 {
     Vec3 { x, y, z }
 }
 
 #[inline(always)]
-pub const fn point3(x: f32, y: f32, z: f32) -> Vec3 {
+pub const fn point3(x: Float, y: Float, z: Float) -> Vec3 {
     Point3 { x, y, z }
 }
 
 #[inline(always)]
-pub const fn color(r: f32, g: f32, b: f32) -> Color {
+pub const fn color(r: Float, g: Float, b: Float) -> Color {
     Color { x: r, y: g, z: b }
 }
 
@@ -114,18 +144,18 @@ impl std::ops::AddAssign for Vec3 {
     }
 }
 
-impl std::ops::Div<f32> for Vec3 {
+impl std::ops::Div<Float> for Vec3 {
     type Output = Vec3;
 
     #[inline]
-    fn div(self, rhs: f32) -> Vec3 {
+    fn div(self, rhs: Float) -> Vec3 {
         self * (1.0 / rhs)
     }
 }
 
-impl std::ops::DivAssign<f32> for Vec3 {
+impl std::ops::DivAssign<Float> for Vec3 {
     #[inline]
-    fn div_assign(&mut self, rhs: f32) {
+    fn div_assign(&mut self, rhs: Float) {
         *self *= 1.0 / rhs
     }
 }
@@ -140,25 +170,25 @@ impl std::ops::Mul<Vec3> for Vec3 {
     }
 }
 
-impl std::ops::Mul<f32> for &Vec3 {
+impl std::ops::Mul<Float> for &Vec3 {
     type Output = Vec3;
 
     #[inline]
-    fn mul(self, rhs: f32) -> Self::Output {
+    fn mul(self, rhs: Float) -> Self::Output {
         vec3(self.x * rhs, self.y * rhs, self.z * rhs)
     }
 }
 
-impl std::ops::Mul<f32> for Vec3 {
+impl std::ops::Mul<Float> for Vec3 {
     type Output = Self;
 
     #[inline]
-    fn mul(self, rhs: f32) -> Self::Output {
+    fn mul(self, rhs: Float) -> Self::Output {
         &self * rhs
     }
 }
 
-impl std::ops::Mul<Vec3> for f32 {
+impl std::ops::Mul<Vec3> for Float {
     type Output = Vec3;
 
     #[inline]
@@ -167,7 +197,7 @@ impl std::ops::Mul<Vec3> for f32 {
     }
 }
 
-impl std::ops::Mul<&Vec3> for f32 {
+impl std::ops::Mul<&Vec3> for Float {
     type Output = Vec3;
 
     #[inline]
@@ -185,9 +215,9 @@ impl std::ops::MulAssign for Vec3 {
     }
 }
 
-impl std::ops::MulAssign<f32> for Vec3 {
+impl std::ops::MulAssign<Float> for Vec3 {
     #[inline]
-    fn mul_assign(&mut self, rhs: f32) {
+    fn mul_assign(&mut self, rhs: Float) {
         self.x *= rhs;
         self.y *= rhs;
         self.z *= rhs;
@@ -222,7 +252,7 @@ impl std::ops::Sub<Vec3> for &Vec3 {
 }
 
 impl std::ops::Index<usize> for Vec3 {
-    type Output = f32;
+    type Output = Float;
 
     #[inline]
     fn index(&self, index: usize) -> &Self::Output {
@@ -342,7 +372,8 @@ mod tests {
 
         let length = (u.x * u.x + u.y * u.y + u.z * u.z).sqrt();
 
-        assert_eq!(1.0, length);
+        // Exact in f32, but one ulp short in f64.
+        assert!((1.0 - length).abs() <= Float::EPSILON);
     }
 
     #[test]
@@ -0,0 +1,376 @@
+use crate::aabb::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// The material index of an empty voxel.
+pub const EMPTY: u16 = 0;
+
+enum Voxels {
+    Dense(Vec<u16>),
+    Sparse(HashMap<[usize; 3], u16>),
+}
+
+// A grid of `dims` cubes of side `voxel_size` from `origin`, each empty or
+// filled with one of `materials`.  Voxel material indices start at 1, so
+// index i is materials[i - 1].  Only the faces between different materials
+// are surfaces, so a solid block of one material has no inner faces.
+pub struct VoxelGrid {
+    origin: Point3,
+    voxel_size: Float,
+    dims: [usize; 3],
+    voxels: Voxels,
+    materials: Vec<Rc<dyn Material>>,
+    bbox: AABB,
+}
+
+impl VoxelGrid {
+    // A grid storing every voxel, for mostly filled volumes.
+    pub fn dense(
+        origin: Point3,
+        voxel_size: Float,
+        dims: [usize; 3],
+        materials: Vec<Rc<dyn Material>>,
+    ) -> Self {
+        let count = dims[0] * dims[1] * dims[2];
+        Self::new(
+            origin,
+            voxel_size,
+            dims,
+            Voxels::Dense(vec![EMPTY; count]),
+            materials,
+        )
+    }
+
+    // A grid storing only the filled voxels, for mostly empty volumes.
+    pub fn sparse(
+        origin: Point3,
+        voxel_size: Float,
+        dims: [usize; 3],
+        materials: Vec<Rc<dyn Material>>,
+    ) -> Self {
+        Self::new(
+            origin,
+            voxel_size,
+            dims,
+            Voxels::Sparse(HashMap::new()),
+            materials,
+        )
+    }
+
+    fn new(
+        origin: Point3,
+        voxel_size: Float,
+        dims: [usize; 3],
+        voxels: Voxels,
+        materials: Vec<Rc<dyn Material>>,
+    ) -> Self {
+        assert!(
+            dims.iter().all(|&n| n > 0),
+            "A voxel grid needs at least one voxel along each axis"
+        );
+
+        let size = voxel_size * vec3(dims[0] as Float, dims[1] as Float, dims[2] as Float);
+
+        Self {
+            origin,
+            voxel_size,
+            dims,
+            voxels,
+            materials,
+            bbox: aabb(origin, origin + size),
+        }
+    }
+
+    pub fn dims(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    pub fn get(&self, cell: [usize; 3]) -> u16 {
+        match &self.voxels {
+            Voxels::Dense(voxels) => voxels[self.index(cell)],
+            Voxels::Sparse(voxels) => voxels.get(&cell).copied().unwrap_or(EMPTY),
+        }
+    }
+
+    pub fn set(&mut self, cell: [usize; 3], material: u16) {
+        assert!(
+            material as usize <= self.materials.len(),
+            "Voxel material index {} is out of range",
+            material
+        );
+
+        let index = self.index(cell);
+        match &mut self.voxels {
+            Voxels::Dense(voxels) => voxels[index] = material,
+            Voxels::Sparse(voxels) => {
+                if material == EMPTY {
+                    voxels.remove(&cell);
+                } else {
+                    voxels.insert(cell, material);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        assert!(
+            x < self.dims[0] && y < self.dims[1] && z < self.dims[2],
+            "Voxel ({}, {}, {}) is outside the grid",
+            x,
+            y,
+            z
+        );
+
+        (z * self.dims[1] + y) * self.dims[0] + x
+    }
+
+    // The record of crossing the face of the voxel at `cell` that is normal
+    // to `axis`, at `t`, into or out of a voxel of `material`.
+    fn record(
+        &self,
+        r: &Ray,
+        t: Float,
+        cell: [usize; 3],
+        axis: usize,
+        outward_normal: Vec3,
+        material: u16,
+    ) -> HitRecord<'_> {
+        let p = r.at(t);
+        let (normal, front_face) = face_normal_and_is_front(r, outward_normal);
+
+        // The position across the face, within the voxel.
+        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+        let local = |a: usize| {
+            let x = (p[a] - self.origin[a]) / self.voxel_size - cell[a] as Float;
+            x.clamp(0.0, 1.0)
+        };
+
+        HitRecord {
+            p,
+            p_error: ray_point_error(r, t),
+            normal,
+            geometric_normal: outward_normal,
+            material: self.materials[material as usize - 1].as_ref(),
+            t,
+            u: local(b),
+            v: local(c),
+            front_face,
+            face: 0,
+            mesh: None,
+            object: None,
+        }
+    }
+
+    // The t of the first face the ray crosses between filled and empty
+    // space, and the arguments to `record` for it.
+    fn intersect(
+        &self,
+        r: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(Float, [usize; 3], usize, Vec3, u16)> {
+        let (t_enter, _) = self.bbox.clip(r, t_min, t_max)?;
+
+        let o = r.origin;
+        let d = r.direction;
+        let unit = |a: usize, sign: Float| {
+            let mut n = vec3(0.0, 0.0, 0.0);
+            n[a] = sign;
+            n
+        };
+
+        // Start in the voxel the ray is in when it enters the grid.
+        let start = r.at(t_enter) - self.origin;
+        let mut cell = [0, 1, 2].map(|a| {
+            let i = (start[a] / self.voxel_size).floor().max(0.0) as usize;
+            i.min(self.dims[a] - 1)
+        });
+
+        // Coming from outside, the face the ray enters through is hit if its
+        // voxel is filled.  Starting inside, such as on a ray spawned within
+        // a glass block, that voxel is what the ray has to leave.
+        let mut current = self.get(cell);
+        if t_enter > t_min {
+            if current != EMPTY {
+                let axis = (0..3)
+                    .filter(|&a| d[a] != 0.0)
+                    .max_by(|&a, &b| {
+                        let entry = |a: usize| {
+                            let plane = if d[a] > 0.0 {
+                                self.bbox.min[a]
+                            } else {
+                                self.bbox.max[a]
+                            };
+                            (plane - o[a]) / d[a]
+                        };
+                        entry(a).total_cmp(&entry(b))
+                    })
+                    .unwrap_or(0);
+                let sign = if d[axis] > 0.0 { -1.0 } else { 1.0 };
+
+                return Some((t_enter, cell, axis, unit(axis, sign), current));
+            }
+            current = EMPTY;
+        }
+
+        // Amanatides and Woo: step to whichever voxel boundary is nearest.
+        let mut step = [0isize; 3];
+        let mut t_next = [Float::INFINITY; 3];
+        let mut t_delta = [Float::INFINITY; 3];
+        for a in 0..3 {
+            if d[a] > 0.0 {
+                step[a] = 1;
+                let plane = self.origin[a] + (cell[a] + 1) as Float * self.voxel_size;
+                t_next[a] = (plane - o[a]) / d[a];
+                t_delta[a] = self.voxel_size / d[a];
+            } else if d[a] < 0.0 {
+                step[a] = -1;
+                let plane = self.origin[a] + cell[a] as Float * self.voxel_size;
+                t_next[a] = (plane - o[a]) / d[a];
+                t_delta[a] = -self.voxel_size / d[a];
+            }
+        }
+
+        loop {
+            let axis = if t_next[0] < t_next[1] {
+                if t_next[0] < t_next[2] {
+                    0
+                } else {
+                    2
+                }
+            } else if t_next[1] < t_next[2] {
+                1
+            } else {
+                2
+            };
+
+            let t = t_next[axis];
+            if t > t_max {
+                return None;
+            }
+
+            let next = cell[axis] as isize + step[axis];
+            let inside = (0..self.dims[axis] as isize).contains(&next);
+            let material = if inside {
+                let mut next_cell = cell;
+                next_cell[axis] = next as usize;
+                self.get(next_cell)
+            } else {
+                EMPTY
+            };
+
+            if material != current && t >= t_min {
+                let sign = step[axis] as Float;
+
+                return Some(if material != EMPTY {
+                    // Into a filled voxel, through its face towards the ray.
+                    let mut next_cell = cell;
+                    next_cell[axis] = next as usize;
+                    (t, next_cell, axis, unit(axis, -sign), material)
+                } else {
+                    // Out of a filled voxel, into empty space.
+                    (t, cell, axis, unit(axis, sign), current)
+                });
+            }
+
+            if !inside {
+                return None;
+            }
+
+            cell[axis] = next as usize;
+            current = material;
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Hittable for VoxelGrid {
+    fn hit(&self, r: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, cell, axis, outward_normal, material) = self.intersect(r, t_min, t_max)?;
+        Some(self.record(r, t, cell, axis, outward_normal, material))
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Float, t_max: Float) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _time0: Float, _time1: Float) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(dense: bool) -> VoxelGrid {
+        let materials: Vec<Rc<dyn Material>> = vec![
+            Rc::new(DiffuseLight::new(color(1.0, 0.0, 0.0))),
+            Rc::new(DiffuseLight::new(color(0.0, 1.0, 0.0))),
+        ];
+        let new = if dense {
+            VoxelGrid::dense
+        } else {
+            VoxelGrid::sparse
+        };
+        let mut grid = new(point3(0.0, 0.0, 0.0), 0.5, [4, 4, 4], materials);
+
+        // A red 2x2x2 block in the corner, with a green voxel beyond it.
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    grid.set([x, y, z], 1);
+                }
+            }
+        }
+        grid.set([3, 0, 0], 2);
+
+        grid
+    }
+
+    #[test]
+    fn walks_the_voxels_a_ray_crosses() {
+        for dense in [true, false] {
+            let grid = grid(dense);
+            let emitted = |rec: &HitRecord| rec.material.emitted(0.0, 0.0, &rec.p);
+
+            // Along +X through the red block, the green voxel is next.
+            let r = Ray::new(point3(-1.0, 0.3, 0.2), vec3(1.0, 0.0, 0.0), 0.0);
+            let rec = grid.hit(&r, 0.0, Float::INFINITY).unwrap();
+            assert!((rec.t - 1.0).abs() < 1e-5);
+            assert_eq!(rec.normal, vec3(-1.0, 0.0, 0.0));
+            assert!(rec.front_face);
+            assert_eq!(emitted(&rec), color(1.0, 0.0, 0.0));
+            assert!((rec.u - 0.6).abs() < 1e-5 && (rec.v - 0.4).abs() < 1e-5);
+
+            // No face between the voxels of the block: leaving it is next.
+            let inside = Ray::new(point3(0.2, 0.3, 0.2), vec3(1.0, 0.0, 0.0), 0.0);
+            let rec = grid.hit(&inside, 0.0, Float::INFINITY).unwrap();
+            assert!((rec.t - 0.8).abs() < 1e-5);
+            assert!(!rec.front_face);
+            assert_eq!(rec.normal, vec3(-1.0, 0.0, 0.0));
+
+            let rec = grid
+                .hit(&inside, rec.next_t(&inside), Float::INFINITY)
+                .unwrap();
+            assert!((rec.t - 1.3).abs() < 1e-5);
+            assert_eq!(emitted(&rec), color(0.0, 1.0, 0.0));
+
+            // Down onto the top of the block, and past it entirely.
+            let down = Ray::new(point3(0.7, 5.0, 0.7), vec3(0.0, -2.0, 0.0), 0.0);
+            let rec = grid.hit(&down, 0.0, Float::INFINITY).unwrap();
+            assert!((rec.t - 2.0).abs() < 1e-5);
+            assert_eq!(rec.normal, vec3(0.0, 1.0, 0.0));
+
+            let past = Ray::new(point3(1.2, 5.0, 1.2), vec3(0.0, -1.0, 0.0), 0.0);
+            assert!(grid.hit(&past, 0.0, Float::INFINITY).is_none());
+            assert!(grid.hit(&r, 0.0, 0.5).is_none());
+        }
+    }
+}